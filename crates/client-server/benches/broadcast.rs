@@ -0,0 +1,92 @@
+//! Time and peak memory of a reliable broadcast to 1000 connected clients, sent at once and in chunks
+//! (see `Server::set_send_chunk_size`). Run with `cargo bench -p client-server --bench broadcast`.
+//! Messages are allocated by GNS outside of the Rust allocator, so memory is measured as the peak
+//! resident set of the process (Linux only, other platforms report time alone)
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr},
+    thread,
+    time::{Duration, Instant},
+};
+
+use client_server::{client::Client, server::Server};
+use omgpp_core::ConnectionState;
+
+const CLIENT_COUNT: usize = 1000;
+const MESSAGE_SIZE: usize = 1024;
+const ROUNDS: u32 = 20;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(60);
+const PUMP_INTERVAL: Duration = Duration::from_millis(1);
+
+// peak resident set since the last reset, in kB
+fn peak_rss_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn reset_peak_rss() {
+    _ = fs::write("/proc/self/clear_refs", "5");
+}
+
+fn pump(server: &Server, clients: &mut [Client]) {
+    _ = server.process::<128>();
+    for client in clients.iter_mut() {
+        _ = client.process::<128>();
+    }
+}
+
+fn connect_clients(server: &Server) -> Vec<Client> {
+    let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let port = server.local_addr().port();
+    let mut clients = (0..CLIENT_COUNT)
+        .map(|_client| Client::new(ip, port))
+        .collect::<Vec<_>>();
+    for client in clients.iter_mut() {
+        client.connect().expect("client must start connecting");
+    }
+    let started_at = Instant::now();
+    while server.connected_players().len() < CLIENT_COUNT
+        || clients.iter().any(|client| client.state() != ConnectionState::Connected)
+    {
+        assert!(
+            started_at.elapsed() < CONNECT_TIMEOUT,
+            "only {} of {CLIENT_COUNT} clients connected",
+            server.connected_players().len()
+        );
+        pump(server, &mut clients);
+        thread::sleep(PUMP_INTERVAL);
+    }
+    clients
+}
+
+fn bench(server: &Server, clients: &mut [Client], chunk_size: Option<usize>) {
+    server.set_send_chunk_size(chunk_size);
+    let data = vec![0xAB; MESSAGE_SIZE];
+    let mut elapsed = Duration::ZERO;
+    let mut peak_kb = 0;
+    for _round in 0..ROUNDS {
+        // drain what the previous round queued, so every round starts with empty GNS queues
+        pump(server, clients);
+        reset_peak_rss();
+        let started_at = Instant::now();
+        server.broadcast_reliable(1, &data).expect("broadcast must be sent");
+        elapsed += started_at.elapsed();
+        peak_kb = peak_kb.max(peak_rss_kb().unwrap_or(0));
+    }
+    let label = match chunk_size {
+        Some(chunk_size) => format!("chunks of {chunk_size}"),
+        None => "at once".to_string(),
+    };
+    println!(
+        "broadcast to {CLIENT_COUNT} clients {label}: {:?} per broadcast, peak resident set {peak_kb} kB",
+        elapsed / ROUNDS
+    );
+}
+
+fn main() {
+    let server = Server::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0).expect("server must listen on a free port");
+    let mut clients = connect_clients(&server);
+    bench(&server, &mut clients, None);
+    bench(&server, &mut clients, Some(64));
+}
@@ -1,28 +1,39 @@
 pub mod ffi;
 
 use std::{
-    cell::{Ref, RefCell},
-    net::IpAddr,
+    cell::{Cell, Ref, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
 };
 
+use either::Either;
 use gns::{GnsSocket, IsClient, IsCreated};
 use gns_sys::{
     k_nSteamNetworkingSend_Reliable, k_nSteamNetworkingSend_Unreliable,
-    ESteamNetworkingConnectionState,
+    k_nSteamNetworkingSend_UnreliableNoDelay, EResult, ESteamNetworkingConnectionState,
 };
 use omgpp_core::{
-    cmd_handler::{CmdHandler, CmdHandlerContainer}, messages::general_message::{
-        general_omgpp_message::{self, CmdRequest, Data},
+    ack::AckReceiver, cmd_handler::{CmdHandler, CmdHandlerContainer}, compression::{decompress, Compression, MAX_DECOMPRESSED_MESSAGE_SIZE, MAX_DECOMPRESSED_SIZE}, control_message::{create_cmd_message, create_rpc_message, create_sequenced_message, ControlMessage}, fragmentation::{create_fragment_messages, FragmentAssembler, MAX_FRAGMENTED_SIZE}, rpc_registry::{RpcArgs, RpcRegistry}, send_flags::SendFlags, typed_message::TypedMessage, messages::general_message::{
+        general_omgpp_message::{CmdRequest, Data},
         GeneralOmgppMessage,
-    }, ConnectionState, Endpoint, OmgppPredefinedCmd, ToEndpoint, TransmitterHelper, GNS
+    }, ConnectionRealTimeStatus, ConnectionState, Endpoint, OmgppPredefinedCmd, ProcessStats, ToConnectionStatus, ToEndpoint, TransmitterHelper, gns, MAX_MESSAGE_SIZE
 };
+#[cfg(feature = "encryption")]
+use omgpp_core::encryption::{Encryption, KEY_SIZE};
 use protobuf::Message;
 use uuid::Uuid;
 
 type OnConnectionChangedCallback = Box<dyn Fn(&Client, &Endpoint, ConnectionState) + 'static>;
-type OnMessageCallback = Box<dyn Fn(&Client, &Endpoint, i64, Vec<u8>) + 'static>;
+type OnMessageCallback = Box<dyn Fn(&Client, &Endpoint, bool, i64, Vec<u8>) + 'static>;
 type OnRpcCallback = Box<dyn Fn(&Client, &Endpoint, bool, i64, u64, i64, Vec<u8>) + 'static>;
 type OnAuthCallback = Box<dyn Fn(&Client, &Endpoint) -> Vec<String> + 'static>;
+type OnRpcReplyCallback = Box<dyn FnOnce(&Client, i64, Vec<u8>) + 'static>;
+
+const END_REASON_MISC_TIMEOUT: i32 = 5002; // k_ESteamNetConnectionEnd_Misc_Timeout
+// decodes the message and invokes the typed callback
+type OnTypedMessageCallback = Box<dyn Fn(&Client, &Endpoint, &[u8]) + 'static>;
+type OnMessageAckedCallback = Box<dyn Fn(&Client, u64) + 'static>;
 
 type ClientResult<T> = Result<T, String>; // TODO replace error with enum
 struct ClientCallbacks {
@@ -30,11 +41,38 @@ struct ClientCallbacks {
     on_message_callback: Option<OnMessageCallback>,
     on_rpc_callback: Option<OnRpcCallback>,
     on_authenticate_callback: Option<OnAuthCallback>,
+    on_typed_message_callbacks: HashMap<i64, OnTypedMessageCallback>,
+    on_message_acked_callback: Option<OnMessageAckedCallback>,
+}
+// delay between reconnect attempts never exceeds it
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+// delivery of reliable messages is checked at most that often, the check queries GNS connection status
+const ACK_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How `Client` reconnects after the connection was lost because of a local problem (e.g. timeout).
+/// The n-th attempt is made `base_delay * 2^n` after the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+/// Which of the resolved addresses `Client::from_hostname` uses.
+/// The first resolved address is used if there is no address of the preferred family
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum AddressPreference {
+    #[default]
+    Any = 0,
+    Ipv4 = 1,
+    Ipv6 = 2,
 }
 //TODO In order to support multiple servers, track multiple GnsSockets
 struct ConnectionTracker {
     server_endpoint: Endpoint,
     state: ConnectionState,
+    reconnect_policy: Option<ReconnectPolicy>,
+    reconnect_attempt: u32,
+    next_reconnect_at: Option<Instant>,
 }
 impl ConnectionTracker {
     fn track_connection_state(&mut self, state: ConnectionState) {
@@ -43,6 +81,36 @@ impl ConnectionTracker {
     fn state(&self) -> ConnectionState {
         self.state.clone()
     }
+    /// Returns false if there is no policy or retries are exhausted
+    fn schedule_reconnect(&mut self) -> bool {
+        self.next_reconnect_at = None;
+        let Some(policy) = self.reconnect_policy else {
+            return false;
+        };
+        if self.reconnect_attempt >= policy.max_retries {
+            return false;
+        }
+        let delay = policy
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(self.reconnect_attempt))
+            .min(MAX_RECONNECT_DELAY);
+        self.reconnect_attempt += 1;
+        self.next_reconnect_at = Some(Instant::now() + delay);
+        true
+    }
+    fn take_due_reconnect(&mut self) -> bool {
+        match self.next_reconnect_at {
+            Some(at) if at <= Instant::now() => {
+                self.next_reconnect_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+    fn reset_reconnect(&mut self) {
+        self.reconnect_attempt = 0;
+        self.next_reconnect_at = None;
+    }
 }
 // TODO In order to support multiple servers, move `socket` in ConnectionTracker
 pub struct Client {
@@ -50,6 +118,29 @@ pub struct Client {
     callbacks: RefCell<ClientCallbacks>,
     connection_tracker: RefCell<ConnectionTracker>,
     cmd_handlers: RefCell<CmdHandlerContainer<Client>>,
+    rpc_registry: RefCell<RpcRegistry<Client>>,
+    fragmentation: Cell<bool>,
+    compression: Cell<Compression>,
+    fragments: RefCell<FragmentAssembler>,
+    next_fragmented_message_id: Cell<u64>,
+    pending_rpc_replies: RefCell<HashMap<u64, OnRpcReplyCallback>>,
+    next_rpc_request_id: Cell<u64>,
+    // numbers of sent reliable messages not yet confirmed by the server,
+    // with offsets of their ends in the reliable stream of the connection
+    unacked_reliable: RefCell<VecDeque<(u64, u64)>>,
+    // bytes of reliable messages sent on the current connection
+    reliable_bytes_sent: Cell<u64>,
+    acks_checked_at: Cell<Option<Instant>>,
+    // message types passed to `on_message`, None means all of them
+    subscriptions: RefCell<Option<HashSet<i64>>>,
+    // received in the auth reply, sent on the next connect to resume the session
+    session_token: RefCell<Option<String>>,
+    // number of the last unreliable message, lets the server drop duplicates (see `Server::set_dedup`)
+    unreliable_sequence: Cell<u64>,
+    // messages the server sent with `Server::send_unreliable_acked`
+    ack_receiver: RefCell<AckReceiver>,
+    #[cfg(feature = "encryption")]
+    encryption: RefCell<Option<Encryption>>,
 }
 impl Client {
     pub fn new(server_ip: IpAddr, server_port: u16) -> Client {
@@ -60,19 +151,60 @@ impl Client {
                 on_message_callback: None,
                 on_rpc_callback: None,
                 on_authenticate_callback:None,
+                on_typed_message_callbacks: HashMap::new(),
+                on_message_acked_callback: None,
             }),
             connection_tracker: RefCell::new(ConnectionTracker {
                 state: ConnectionState::None,
+                reconnect_policy: None,
+                reconnect_attempt: 0,
+                next_reconnect_at: None,
                 server_endpoint: Endpoint {
                     ip: server_ip,
                     port: server_port,
                 },
             }),
             cmd_handlers: RefCell::new(CmdHandlerContainer::new()),
+            rpc_registry: RefCell::new(RpcRegistry::new()),
+            fragmentation: Cell::new(false),
+            compression: Cell::new(Compression::None),
+            fragments: Default::default(),
+            next_fragmented_message_id: Cell::new(0),
+            pending_rpc_replies: Default::default(),
+            unacked_reliable: Default::default(),
+            reliable_bytes_sent: Cell::new(0),
+            acks_checked_at: Cell::new(None),
+            subscriptions: Default::default(),
+            session_token: Default::default(),
+            unreliable_sequence: Cell::new(0),
+            ack_receiver: Default::default(),
+            #[cfg(feature = "encryption")]
+            encryption: RefCell::new(None),
+            next_rpc_request_id: Cell::new(0),
         };
         client.init_default_cmd_handlers();
         client
     }
+    /// Resolves `host` (blocking DNS lookup) and creates a client for one of the resolved addresses
+    pub fn from_hostname(
+        host: &str,
+        port: u16,
+        preference: AddressPreference,
+    ) -> ClientResult<Client> {
+        let addresses = (host, port)
+            .to_socket_addrs()
+            .or_else(|err| Err(format!("Cannot resolve {}: {}", host, err)))?
+            .collect::<Vec<_>>();
+        let preferred = addresses.iter().find(|address| match preference {
+            AddressPreference::Any => true,
+            AddressPreference::Ipv4 => address.is_ipv4(),
+            AddressPreference::Ipv6 => address.is_ipv6(),
+        });
+        let address: &SocketAddr = preferred
+            .or(addresses.first())
+            .ok_or(format!("No addresses found for {}", host))?;
+        Ok(Client::new(address.ip(), port))
+    }
     fn init_default_cmd_handlers(&self) {
         let mut cmd_handlers = self.cmd_handlers.borrow_mut();
         _ = cmd_handlers.register_handler(CmdHandler::new(
@@ -80,6 +212,21 @@ impl Client {
             false,
             Box::new(Client::cmd_auth_handle),
         ));
+        _ = cmd_handlers.register_handler(CmdHandler::new(
+            OmgppPredefinedCmd::PING,
+            false,
+            Box::new(Client::cmd_ping_handle),
+        ));
+    }
+    // echo server ping back so the server can measure round-trip time
+    fn cmd_ping_handle(
+        &self,
+        _: &Uuid, // not used in client
+        _endpoint: &Endpoint,
+        _: &CmdHandler<Client>,
+        request: &CmdRequest,
+    ) {
+        _ = self.send_cmd(OmgppPredefinedCmd::PING, request.request_id, None);
     }
     fn cmd_auth_handle(
         &self,
@@ -94,7 +241,14 @@ impl Client {
                 self.connection_tracker
                     .borrow_mut()
                     .track_connection_state(ConnectionState::Connected);
-                let new_state = self.connection_tracker.borrow().state();
+                // server returns the same token if the previous session was resumed
+                let token = request.args.get(1).cloned();
+                let resumed = token.is_some() && *self.session_token.borrow() == token;
+                *self.session_token.borrow_mut() = token;
+                let new_state = match resumed {
+                    true => ConnectionState::Reconnected,
+                    false => self.connection_tracker.borrow().state(),
+                };
                 let callbacks = self.callbacks.borrow();
                 if let Some(cb) = &callbacks.on_connection_changed_callback {
                     cb(self, endpoint, new_state);
@@ -108,9 +262,10 @@ impl Client {
     ) {
         self.callbacks.borrow_mut().on_connection_changed_callback = Some(Box::from(callback));
     }
+    /// Callback receives whether the message was sent reliably and the message type
     pub fn register_on_message(
         &self,
-        callback: impl Fn(&Client, &Endpoint, i64, Vec<u8>) + 'static,
+        callback: impl Fn(&Client, &Endpoint, bool, i64, Vec<u8>) + 'static,
     ) {
         self.callbacks.borrow_mut().on_message_callback = Some(Box::from(callback));
     }
@@ -123,7 +278,118 @@ impl Client {
     pub fn register_on_auth(&self,callback: impl Fn(&Client, &Endpoint)->Vec<String> + 'static){
         self.callbacks.borrow_mut().on_authenticate_callback = Some(Box::from(callback));
     }
+    /// Callback receives decoded messages of type `M::MSG_TYPE` instead of `on_message`.
+    /// Messages which cannot be decoded are dropped
+    pub fn register_on_typed_message<M: TypedMessage + 'static>(
+        &self,
+        callback: impl Fn(&Client, &Endpoint, M) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_typed_message_callbacks.insert(
+            M::MSG_TYPE,
+            Box::new(move |client, endpoint, data| {
+                if let Ok(message) = M::decode(data) {
+                    callback(client, endpoint, message)
+                }
+            }),
+        );
+    }
+    pub fn unregister_on_typed_message<M: TypedMessage>(&self) {
+        self.callbacks
+            .borrow_mut()
+            .on_typed_message_callbacks
+            .remove(&M::MSG_TYPE);
+    }
+    pub fn unregister_on_connection_state_changed(&self) {
+        self.callbacks.borrow_mut().on_connection_changed_callback = None;
+    }
+    pub fn unregister_on_message(&self) {
+        self.callbacks.borrow_mut().on_message_callback = None;
+    }
+    /// Once anything is subscribed `on_message` is only invoked for subscribed message types,
+    /// other messages are dropped. Typed message callbacks are not affected
+    pub fn subscribe(&self, msg_type: i64) {
+        self.subscriptions
+            .borrow_mut()
+            .get_or_insert_with(HashSet::new)
+            .insert(msg_type);
+    }
+    /// Unsubscribing the last type does not restore receiving all types, see `subscribe_all`
+    pub fn unsubscribe(&self, msg_type: i64) {
+        if let Some(subscriptions) = self.subscriptions.borrow_mut().as_mut() {
+            subscriptions.remove(&msg_type);
+        }
+    }
+    /// Clears subscriptions, `on_message` is invoked for every message type again (default)
+    pub fn subscribe_all(&self) {
+        self.subscriptions.replace(None);
+    }
+    /// Invoked with the message number returned by `send_reliable` once the server confirmed delivery, in send order.
+    /// GNS reports only how many reliable bytes are unacknowledged, so delivery is derived from that count
+    /// and checked every 20ms. A message acknowledged out of order (its packet was lost while later ones arrived)
+    /// may be reported before its retransmission is acknowledged; it is still delivered unless the connection is lost.
+    /// Messages unacked when the connection is lost are never reported
+    pub fn register_on_message_acked(&self, callback: impl Fn(&Client, u64) + 'static) {
+        self.callbacks.borrow_mut().on_message_acked_callback = Some(Box::from(callback));
+    }
+    pub fn unregister_on_message_acked(&self) {
+        self.callbacks.borrow_mut().on_message_acked_callback = None;
+    }
+    pub fn unregister_on_rpc(&self) {
+        self.callbacks.borrow_mut().on_rpc_callback = None;
+    }
+    /// Calls of `method_id` made by the server are dispatched to `handler` instead of `on_rpc`.
+    /// Returned args are sent back as the reply with the call's `request_id`, `None` sends no reply.
+    /// Uuid passed to the handler is nil
+    pub fn register_rpc_method(
+        &self,
+        method_id: i64,
+        handler: impl Fn(&Client, &Uuid, &Endpoint, RpcArgs) -> Option<RpcArgs> + 'static,
+    ) -> ClientResult<()> {
+        self.rpc_registry
+            .borrow_mut()
+            .register(method_id, Box::new(handler))
+    }
+    /// Returns false if the method was not registered
+    pub fn unregister_rpc_method(&self, method_id: i64) -> bool {
+        self.rpc_registry.borrow_mut().unregister(method_id)
+    }
+    pub fn unregister_on_auth(&self) {
+        self.callbacks.borrow_mut().on_authenticate_callback = None;
+    }
+    /// Splits reliable messages which do not fit into a single GNS message into fragments
+    /// and reassembles received fragments before `on_message` callback is invoked.
+    /// Must be enabled on both sides. Messages above `MAX_FRAGMENTED_SIZE` fail to be sent
+    pub fn set_fragmentation(&self, enabled: bool) {
+        self.fragmentation.set(enabled);
+    }
+    /// Compresses regular messages above `COMPRESSION_THRESHOLD` bytes.
+    /// Received messages are decompressed regardless of the setting
+    pub fn set_compression(&self, compression: Compression) {
+        self.compression.set(compression);
+    }
+    /// Encrypts data of regular messages with the pre-shared key, it must be the same as the server's one
+    /// (see `Server::set_encryption_key`). Messages which fail to decrypt are dropped. `None` disables it
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&self, key: Option<&[u8; KEY_SIZE]>) {
+        *self.encryption.borrow_mut() = key.map(Encryption::new);
+    }
+    /// Enables auto reconnect after `ProblemDetectedLocally`. `max_retries == 0` disables it
+    pub fn set_reconnect_policy(&self, max_retries: u32, base_delay: Duration) {
+        let mut tracker = self.connection_tracker.borrow_mut();
+        tracker.reconnect_policy = match max_retries {
+            0 => None,
+            _ => Some(ReconnectPolicy {
+                max_retries,
+                base_delay,
+            }),
+        };
+        tracker.reset_reconnect();
+    }
     pub fn connect(&mut self) -> ClientResult<()> {
+        self.connection_tracker.borrow_mut().reset_reconnect();
+        self.open_connection()
+    }
+    fn open_connection(&mut self) -> ClientResult<()> {
         let old_socket = &self.socket;
         let tracker = &self.connection_tracker.borrow();
         let current_connection_state = &tracker.state;
@@ -134,8 +400,9 @@ impl Client {
             }
             _ => (),
         }
-        let gns = GNS.as_ref()?;
-        let gns_socket = GnsSocket::<IsCreated>::new(&gns.global, &gns.utils).unwrap();
+        let gns = gns()?;
+        let gns_socket = GnsSocket::<IsCreated>::new(&gns.global, &gns.utils)
+            .ok_or("Cannot create socket".to_string())?;
 
         let address_to_connect = match tracker.server_endpoint.ip {
             IpAddr::V4(v4) => v4.to_ipv6_mapped(),
@@ -151,10 +418,44 @@ impl Client {
     }
 
     pub fn disconnect(&self) {
+        self.connection_tracker.borrow_mut().reset_reconnect();
         if let Some(socket) = &self.socket {
             socket.close_connection(socket.connection(), 0, "", false);
         }
     }
+    /// Switches to another server (e.g. from a lobby to a match server) keeping registered callbacks.
+    /// The current connection is closed and reported as `Disconnected`, call `connect` afterwards.
+    /// The session token belongs to the previous server, so it is dropped
+    pub fn set_target(&mut self, server_ip: IpAddr, server_port: u16) {
+        self.disconnect();
+        // locally closed connections are not reported by GNS
+        let old_state = self.connection_tracker.borrow().state();
+        if self.socket.take().is_some()
+            && !matches!(
+                old_state,
+                ConnectionState::None | ConnectionState::Disconnected | ConnectionState::TimedOut
+            )
+        {
+            self.connection_tracker
+                .borrow_mut()
+                .track_connection_state(ConnectionState::Disconnected);
+            let endpoint = self.connection_tracker.borrow().server_endpoint.clone();
+            if let Some(cb) = &self.callbacks.borrow().on_connection_changed_callback {
+                cb(self, &endpoint, ConnectionState::Disconnected);
+            }
+        }
+        *self.fragments.borrow_mut() = FragmentAssembler::default();
+        self.pending_rpc_replies.borrow_mut().clear();
+        self.unacked_reliable.borrow_mut().clear();
+        self.reliable_bytes_sent.set(0);
+        self.unreliable_sequence.set(0);
+        *self.ack_receiver.borrow_mut() = AckReceiver::default();
+        *self.session_token.borrow_mut() = None;
+        self.connection_tracker.borrow_mut().server_endpoint = Endpoint {
+            ip: server_ip,
+            port: server_port,
+        };
+    }
     pub fn send_cmd(
         &self,
         cmd: &str,
@@ -175,14 +476,20 @@ impl Client {
             Err("Socket not connected; Make sure to call `connect`".to_string())
         }
     }
-    pub fn process<const N: usize>(&self) -> ClientResult<()> {
+    /// Handles up to `N` connection events and `N` messages. Returns how many were handled,
+    /// so the caller can call it again until the queue is drained
+    pub fn process<const N: usize>(&mut self) -> ClientResult<ProcessStats> {
+        let should_reconnect = self.connection_tracker.borrow_mut().take_due_reconnect();
+        if should_reconnect && self.open_connection().is_err() {
+            self.connection_tracker.borrow_mut().schedule_reconnect();
+        }
         if self.socket.is_none() {
             return Err("Socket not initialized".to_string());
         }
         let socket = self.socket.as_ref().unwrap();
         socket.poll_callbacks();
         let mut socket_op_is_success = ClientResult::Ok(());
-        let _processed_event_count = socket.poll_event::<N>(|event| {
+        let events = socket.poll_event::<N>(|event| {
             Client::process_connection_events(
                 &self,
                 event,
@@ -190,19 +497,139 @@ impl Client {
                 &self.connection_tracker,
             );
         });
-        let _processed_msg_count = socket.poll_messages::<N>(|msg| {
+        let messages = socket.poll_messages::<N>(|msg| {
             socket_op_is_success =
                 Client::process_messages(self, msg, &self.connection_tracker, &self.callbacks);
         });
-        socket_op_is_success
+        self.notify_acked_messages();
+        self.send_ack();
+        socket_op_is_success.map(|_| ProcessStats { events, messages })
+    }
+    // GNS does not report acks of individual messages, only the number of reliable bytes still queued or unacked.
+    // A message is reported once no more bytes are outstanding than were sent after it. GNS framing and
+    // reliable commands are not counted in `reliable_bytes_sent`, which only delays the report
+    fn notify_acked_messages(&self) {
+        if self.unacked_reliable.borrow().is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(checked_at) = self.acks_checked_at.get() {
+            if now - checked_at < ACK_CHECK_INTERVAL {
+                return;
+            }
+        }
+        self.acks_checked_at.set(Some(now));
+        let Ok(status) = self.connection_info() else {
+            return;
+        };
+        let outstanding = (status.pending_reliable + status.sent_unacked_reliable).max(0) as u64;
+        let acked_bytes = self.reliable_bytes_sent.get().saturating_sub(outstanding);
+        let mut acked = Vec::new();
+        {
+            let mut unacked_reliable = self.unacked_reliable.borrow_mut();
+            while let Some((message_number, end)) = unacked_reliable.front().cloned() {
+                if end > acked_bytes {
+                    break;
+                }
+                unacked_reliable.pop_front();
+                acked.push(message_number);
+            }
+        }
+        if let Some(cb) = &self.callbacks.borrow().on_message_acked_callback {
+            for message_number in acked {
+                cb(self, message_number);
+            }
+        }
     }
 
-    pub fn send(&self, msg_type: i64, data: &[u8]) -> ClientResult<()> {
+    // acknowledges messages received since the last call, once per `process` call.
+    // Sent unreliably, the server retransmits messages whose ack was lost and they are acked again
+    fn send_ack(&self) {
+        let Some(socket) = &self.socket else {
+            return;
+        };
+        let Some((latest, bits)) = self.ack_receiver.borrow_mut().take_ack() else {
+            return;
+        };
+        let args = vec![latest.to_string(), bits.to_string()];
+        let Ok(cmd_bytes) = create_cmd_message(String::from(OmgppPredefinedCmd::ACK), 0, args) else {
+            return;
+        };
+        _ = TransmitterHelper::send(
+            socket,
+            &[socket.connection()],
+            k_nSteamNetworkingSend_Unreliable,
+            &cmd_bytes,
+        );
+    }
+
+    /// Token of the current (or last) session. It is sent on reconnect, so the server keeps the client's uuid.
+    /// Can be saved and passed to `set_session_token` of a new client to resume the session after restart
+    pub fn session_token(&self) -> Option<String> {
+        self.session_token.borrow().clone()
+    }
+    /// `None` makes the next connection start a new session
+    pub fn set_session_token(&self, token: Option<String>) {
+        *self.session_token.borrow_mut() = token;
+    }
+    /// Current state of the connection to the server, `Connected` only after authentication
+    pub fn state(&self) -> ConnectionState {
+        self.connection_tracker.borrow().state()
+    }
+    /// True once the client passed authentication, until the connection is closed
+    pub fn is_connected(&self) -> bool {
+        self.state() == ConnectionState::Connected
+    }
+    /// Real-time status of the connection to the server: ping, connection quality, rates and queued bytes
+    pub fn connection_info(&self) -> ClientResult<ConnectionRealTimeStatus> {
+        let Some(socket) = &self.socket else {
+            return Err("Socket not connected; Make sure to call `connect`".to_string());
+        };
+        let (status, _lanes) = socket
+            .get_connection_real_time_status(socket.connection(), 0)
+            .or(Err("Cannot get connection status".to_string()))?;
+        Ok(status.to_connection_status())
+    }
+    /// Bytes queued in the connection's outgoing buffer and not sent yet (reliable and unreliable).
+    /// Use it as backpressure before queueing more data
+    pub fn pending_bytes(&self) -> ClientResult<usize> {
+        let status = self.connection_info()?;
+        Ok((status.pending_reliable + status.pending_unreliable).max(0) as usize)
+    }
+    /// Whether there is still queued data or reliable data not acknowledged by the server
+    pub fn is_sending(&self) -> ClientResult<bool> {
+        let status = self.connection_info()?;
+        Ok(status.pending_reliable + status.pending_unreliable + status.sent_unacked_reliable > 0)
+    }
+    /// Returns GNS message number of the sent message
+    pub fn send(&self, msg_type: i64, data: &[u8]) -> ClientResult<u64> {
         self.send_with_flags(k_nSteamNetworkingSend_Unreliable, msg_type, data)
     }
-    pub fn send_reliable(&self, msg_type: i64, data: &[u8]) -> ClientResult<()> {
+    /// Returns GNS message number of the sent message (of its last fragment if it was fragmented),
+    /// which is later passed to `on_message_acked` once the server received it
+    pub fn send_reliable(&self, msg_type: i64, data: &[u8]) -> ClientResult<u64> {
         self.send_with_flags(k_nSteamNetworkingSend_Reliable, msg_type, data)
     }
+    /// Sends the message with arbitrary GNS flags, e.g. `SendFlags::UNRELIABLE | SendFlags::NO_NAGLE`
+    pub fn send_with(&self, flags: SendFlags, msg_type: i64, data: &[u8]) -> ClientResult<u64> {
+        self.send_with_flags(flags.bits(), msg_type, data)
+    }
+    /// Same as `send_reliable`. Reliable messages are delivered exactly once and in the order they were sent, like over TCP
+    pub fn send_reliable_ordered(&self, msg_type: i64, data: &[u8]) -> ClientResult<u64> {
+        self.send_reliable(msg_type, data)
+    }
+    /// Unreliable send which is never queued (`k_nSteamNetworkingSend_UnreliableNoDelay`): the message goes out
+    /// right away, without waiting for Nagle, or fails when it can't. It may be lost or delivered out of order
+    /// relative to other messages, but never arrives late, e.g. for positions superseded by the next update
+    pub fn send_unreliable_unordered(&self, msg_type: i64, data: &[u8]) -> ClientResult<u64> {
+        self.send_with_flags(k_nSteamNetworkingSend_UnreliableNoDelay, msg_type, data)
+    }
+    pub fn send_msg<M: TypedMessage>(&self, message: &M) -> ClientResult<u64> {
+        self.send(M::MSG_TYPE, &message.encode())
+    }
+    pub fn send_msg_reliable<M: TypedMessage>(&self, message: &M) -> ClientResult<u64> {
+        self.send_reliable(M::MSG_TYPE, &message.encode())
+    }
 
     pub fn call_rpc(
         &self,
@@ -211,9 +638,31 @@ impl Client {
         request_id: u64,
         arg_type: i64,
         arg_data: Option<&[u8]>,
+    ) -> ClientResult<()> {
+        self.send_rpc(reliable, method_id, request_id, arg_type, arg_data, false)
+    }
+    /// Replies to the server's rpc call. `request_id` must be the one received in `on_rpc`
+    pub fn reply_rpc(
+        &self,
+        reliable: bool,
+        method_id: i64,
+        request_id: u64,
+        arg_type: i64,
+        arg_data: Option<&[u8]>,
+    ) -> ClientResult<()> {
+        self.send_rpc(reliable, method_id, request_id, arg_type, arg_data, true)
+    }
+    fn send_rpc(
+        &self,
+        reliable: bool,
+        method_id: i64,
+        request_id: u64,
+        arg_type: i64,
+        arg_data: Option<&[u8]>,
+        is_reply: bool,
     ) -> ClientResult<()> {
         if let Some(socket) = &self.socket {
-            let msg_bytes = create_rpc_message(reliable, method_id, request_id, arg_type, arg_data)
+            let msg_bytes = create_rpc_message(reliable, method_id, request_id, arg_type, arg_data, is_reply)
                 .or_else(|_or| Err("Cannot create rpc message".to_string()))?;
 
             let flags = match reliable {
@@ -221,23 +670,121 @@ impl Client {
                 false => k_nSteamNetworkingSend_Unreliable,
             };
 
-            // TODO check send result
-            let _send_results =
+            let send_results =
                 TransmitterHelper::send(socket, &[socket.connection()], flags, &msg_bytes);
+            Client::check_send_results(send_results).map(|_message_number| ())
+        } else {
+            Err("Socket not connected; Make sure to call `connect`".to_string())
         }
-        Ok(())
     }
 
-    fn send_with_flags(&self, flags: i32, msg_type: i64, data: &[u8]) -> ClientResult<()> {
-        if let Some(socket) = &self.socket {
-            let msg_bytes = create_general_message(msg_type, data)
-                .or_else(|_err| Err("Cannot create general message"))?;
+    /// Calls rpc and invokes `callback` with reply's `arg_type` and `arg_data` once the server answers with `Server::reply_rpc`.
+    /// Returns generated request id. Pending callbacks are dropped on disconnect
+    pub fn call_rpc_with_reply(
+        &self,
+        reliable: bool,
+        method_id: i64,
+        arg_type: i64,
+        arg_data: Option<&[u8]>,
+        callback: impl FnOnce(&Client, i64, Vec<u8>) + 'static,
+    ) -> ClientResult<u64> {
+        if self.socket.is_none() {
+            return Err("Socket not connected; Make sure to call `connect`".to_string());
+        }
+        let request_id = self.next_rpc_request_id.get();
+        self.next_rpc_request_id.set(request_id.wrapping_add(1));
+        self.pending_rpc_replies
+            .borrow_mut()
+            .insert(request_id, Box::new(callback));
+        if let Err(err) = self.call_rpc(reliable, method_id, request_id, arg_type, arg_data) {
+            self.pending_rpc_replies.borrow_mut().remove(&request_id);
+            return Err(err);
+        }
+        Ok(request_id)
+    }
 
-            // TODO check send result
-            let _send_results =
+    fn send_with_flags(&self, flags: i32, msg_type: i64, data: &[u8]) -> ClientResult<u64> {
+        let (message_number, bytes_sent) = self.send_regular_message(flags, msg_type, data)?;
+        if flags & k_nSteamNetworkingSend_Reliable != 0 {
+            let end = self.reliable_bytes_sent.get() + bytes_sent as u64;
+            self.reliable_bytes_sent.set(end);
+            self.unacked_reliable.borrow_mut().push_back((message_number, end));
+        }
+        Ok(message_number)
+    }
+    // returns number of the sent message (of its last fragment) and encoded bytes sent
+    fn send_regular_message(&self, flags: i32, msg_type: i64, data: &[u8]) -> ClientResult<(u64, usize)> {
+        let Some(socket) = &self.socket else {
+            return Err("Socket not connected; Make sure to call `connect`".to_string());
+        };
+        if ControlMessage::is_reserved(msg_type) {
+            Err(format!("Message type {} is reserved, application types must be non-negative", msg_type))?
+        }
+        let is_reliable = flags & k_nSteamNetworkingSend_Reliable != 0;
+        let sequence = match is_reliable {
+            true => 0,
+            false => {
+                let sequence = self.unreliable_sequence.get().wrapping_add(1).max(1);
+                self.unreliable_sequence.set(sequence);
+                sequence
+            }
+        };
+        let (compression, payload) = self.compression.get().compress(data);
+        let payload = self.encrypt_payload(payload)?;
+        let msg_bytes = create_sequenced_message(msg_type, &payload, compression, sequence)
+            .or_else(|_err| Err("Cannot create general message"))?;
+        // the server decompresses a single message up to `MAX_DECOMPRESSED_MESSAGE_SIZE`
+        if msg_bytes.len() <= MAX_MESSAGE_SIZE && data.len() <= MAX_DECOMPRESSED_MESSAGE_SIZE {
+            let send_results =
                 TransmitterHelper::send(socket, &[socket.connection()], flags, &msg_bytes);
+            let message_number = Client::check_send_results(send_results)?;
+            return Ok((message_number, msg_bytes.len()));
+        }
+        if !is_reliable || !self.fragmentation.get() {
+            let size = msg_bytes.len().max(data.len());
+            Err(format!("Message is too large: {} bytes, max is {} bytes", size, MAX_MESSAGE_SIZE))?
+        }
+        let size = payload.len().max(data.len());
+        if size > MAX_FRAGMENTED_SIZE {
+            Err(format!("Message is too large: {} bytes, max is {} bytes", size, MAX_FRAGMENTED_SIZE))?
+        }
+        let message_id = self.next_fragmented_message_id.get();
+        self.next_fragmented_message_id.set(message_id.wrapping_add(1));
+        let fragments = create_fragment_messages(message_id, msg_type, compression, &payload)
+            .or_else(|_err| Err("Cannot create fragment message"))?;
+        let mut message_number = 0;
+        let mut bytes_sent = 0;
+        for fragment in fragments {
+            let send_results =
+                TransmitterHelper::send(socket, &[socket.connection()], flags, &fragment);
+            message_number = Client::check_send_results(send_results)?;
+            bytes_sent += fragment.len();
+        }
+        Ok((message_number, bytes_sent))
+    }
+    // payload of regular messages is encrypted after compression
+    fn encrypt_payload(&self, payload: Vec<u8>) -> ClientResult<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption) = self.encryption.borrow().as_ref() {
+            return encryption.encrypt(&payload);
+        }
+        Ok(payload)
+    }
+    // decrypts and decompresses the payload of a received regular message, see `decompress` for `max_size`
+    fn open_payload(&self, compression: u32, payload: Vec<u8>, max_size: usize) -> ClientResult<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        let payload = match self.encryption.borrow().as_ref() {
+            Some(encryption) => encryption.decrypt(&payload)?,
+            None => payload,
+        };
+        decompress(compression, payload, max_size)
+    }
+    fn check_send_results(send_results: Vec<Either<u64, EResult>>) -> ClientResult<u64> {
+        match send_results.first() {
+            Some(Either::Left(message_number)) => Ok(*message_number),
+            Some(Either::Right(result)) => Err(format!("Message was not sent: {:?}", result)),
+            None => Err("Message was not sent".to_string()),
         }
-        Ok(())
     }
     fn process_connection_events(
         &self,
@@ -266,7 +813,24 @@ impl Client {
                 |ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ClosedByPeer
                 |ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ProblemDetectedLocally,
             ) => {
-                connection_tracker.borrow_mut().track_connection_state(ConnectionState::Disconnected);
+                // problems other than timeout (e.g. internal GNS errors) are reported as a plain disconnect
+                let new_state = match event.info().end_reason() as i32 {
+                    END_REASON_MISC_TIMEOUT => ConnectionState::TimedOut,
+                    _ => ConnectionState::Disconnected,
+                };
+                connection_tracker.borrow_mut().track_connection_state(new_state);
+                *self.fragments.borrow_mut() = FragmentAssembler::default();
+                self.pending_rpc_replies.borrow_mut().clear();
+                self.unacked_reliable.borrow_mut().clear();
+                self.reliable_bytes_sent.set(0);
+                self.unreliable_sequence.set(0);
+                *self.ack_receiver.borrow_mut() = AckReceiver::default();
+                if matches!(
+                    event.info().state(),
+                    ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ProblemDetectedLocally
+                ) {
+                    connection_tracker.borrow_mut().schedule_reconnect();
+                }
                 let new_state = connection_tracker.borrow().state();
                 if let Some(cb) = &callbacks.borrow().on_connection_changed_callback {
                     cb(self,&endpoint, new_state);
@@ -278,10 +842,15 @@ impl Client {
                 ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
             ) => {
                 connection_tracker.borrow_mut().track_connection_state(ConnectionState::ConnectedUnverified);
+                connection_tracker.borrow_mut().reset_reconnect();
                 let new_state = connection_tracker.borrow().state();
                 if let Some(cb) = &callbacks.borrow().on_connection_changed_callback {
                     cb(self,&endpoint, new_state);
                 }
+                let session_token = self.session_token.borrow().clone();
+                if let Some(token) = session_token {
+                    _ = self.send_cmd(OmgppPredefinedCmd::RESUME, 0, Some(vec![token]));
+                }
                 let mut auth_params:Option<Vec<String>> = None;
                 if let Some(cb) = &callbacks.borrow().on_authenticate_callback{
                     auth_params = Some(cb(self,&endpoint));
@@ -293,6 +862,33 @@ impl Client {
         }
     }
 
+    fn notify_message(
+        &self,
+        sender: &Endpoint,
+        reliable: bool,
+        msg_type: i64,
+        data: Vec<u8>,
+        callbacks: &RefCell<ClientCallbacks>,
+    ) {
+        // internal regular messages (`ControlMessage::FLUSH`) are dropped before decoding, other types are unknown
+        if ControlMessage::is_reserved(msg_type) {
+            return;
+        }
+        let callbacks = callbacks.borrow();
+        if let Some(typed_cb) = callbacks.on_typed_message_callbacks.get(&msg_type) {
+            typed_cb(self, sender, &data);
+            return;
+        }
+        if let Some(subscriptions) = self.subscriptions.borrow().as_ref() {
+            if !subscriptions.contains(&msg_type) {
+                return;
+            }
+        }
+        // cb stands for callback
+        if let Some(cb) = &callbacks.on_message_callback {
+            cb(self, sender, reliable, msg_type, data)
+        }
+    }
     fn process_messages(
         &self,
         gns_msg: &gns::GnsNetworkMessage<gns::ToReceive>,
@@ -300,17 +896,66 @@ impl Client {
         callbacks: &RefCell<ClientCallbacks>,
     ) -> ClientResult<()> {
         let data = gns_msg.payload();
+        let reliable = gns_msg.flags() & k_nSteamNetworkingSend_Reliable != 0;
         let sender = connection_tracker.borrow().server_endpoint.clone();
         if let Some(decoded) = GeneralOmgppMessage::parse_from_bytes(data).ok() {
             // we decoded the message
             match decoded.data {
+                // sent by `Server::flush_player` only to push queued data out
+                Some(Data::Message(message)) if message.type_ == ControlMessage::FLUSH => (),
                 Some(Data::Message(message)) => {
-                    // cb stands for callback
-                    if let Some(cb) = &callbacks.borrow().on_message_callback {
-                        cb(self, &sender, message.type_, message.data)
+                    if message.ack_requested && !self.ack_receiver.borrow_mut().receive(message.sequence) {
+                        return Ok(());
+                    }
+                    if let Ok(msg_data) =
+                        self.open_payload(message.compression, message.data, MAX_DECOMPRESSED_MESSAGE_SIZE)
+                    {
+                        self.notify_message(&sender, reliable, message.type_, msg_data, callbacks);
+                    }
+                }
+                Some(Data::Fragment(fragment)) => {
+                    if !self.fragmentation.get() {
+                        return Ok(());
+                    }
+                    let message = self.fragments.borrow_mut().push(fragment);
+                    let Some((msg_type, compression, msg_data)) = message else {
+                        return Ok(());
+                    };
+                    if let Ok(msg_data) = self.open_payload(compression, msg_data, MAX_DECOMPRESSED_SIZE) {
+                        // fragments are always sent reliably
+                        self.notify_message(&sender, true, msg_type, msg_data, callbacks);
                     }
                 }
                 Some(Data::Rpc(rpc_call)) => {
+                    if rpc_call.is_reply {
+                        let reply_callback = self
+                            .pending_rpc_replies
+                            .borrow_mut()
+                            .remove(&rpc_call.request_id);
+                        if let Some(reply_callback) = reply_callback {
+                            reply_callback(self, rpc_call.arg_type, rpc_call.arg_data);
+                            return Ok(());
+                        }
+                    } else if self.rpc_registry.borrow().contains(rpc_call.method_id) {
+                        let args = RpcArgs::new(rpc_call.arg_type, rpc_call.arg_data);
+                        let reply = self.rpc_registry.borrow().handle(
+                            self,
+                            &Uuid::nil(),
+                            &sender,
+                            rpc_call.method_id,
+                            args,
+                        );
+                        if let Some(reply) = reply {
+                            _ = self.reply_rpc(
+                                rpc_call.reliable,
+                                rpc_call.method_id,
+                                rpc_call.request_id,
+                                reply.arg_type,
+                                Some(&reply.data),
+                            );
+                        }
+                        return Ok(());
+                    }
                     if let Some(rpc_callback) = &callbacks.borrow().on_rpc_callback {
                         rpc_callback(
                             self,
@@ -337,48 +982,3 @@ impl Client {
     }
 }
 
-fn create_general_message(msg_type: i64, data: &[u8]) -> protobuf::Result<Vec<u8>> {
-    let mut payload = GeneralOmgppMessage::new();
-    let mut message = general_omgpp_message::Message::new();
-    message.type_ = msg_type;
-    message.data = Vec::from(data); // somehow get rid of unessesary array copying
-    payload.data = Some(Data::Message(message));
-    let bytes = payload.write_to_bytes()?;
-    return Ok(bytes);
-}
-fn create_rpc_message(
-    reliable: bool,
-    method_id: i64,
-    request_id: u64,
-    arg_type: i64,
-    data: Option<&[u8]>,
-) -> protobuf::Result<Vec<u8>> {
-    let mut payload = GeneralOmgppMessage::new();
-    let mut rpc = general_omgpp_message::RpcCall::new();
-    rpc.reliable = reliable;
-    rpc.method_id = method_id;
-    rpc.request_id = request_id;
-    rpc.arg_type = arg_type;
-    rpc.arg_data = match data {
-        Some(byte_array) => Vec::from(byte_array),
-        None => Vec::new(),
-    };
-    payload.data = Some(Data::Rpc(rpc));
-    let bytes = payload.write_to_bytes()?;
-    return Ok(bytes);
-}
-
-fn create_cmd_message(
-    cmd: String,
-    request_id: u64,
-    args: Vec<String>,
-) -> protobuf::Result<Vec<u8>> {
-    let mut payload = GeneralOmgppMessage::new();
-    let mut request = general_omgpp_message::CmdRequest::new();
-    request.cmd = cmd;
-    request.request_id = request_id;
-    request.args = args;
-    payload.data = Some(Data::Cmd(request));
-    let bytes = payload.write_to_bytes()?;
-    return Ok(bytes);
-}
@@ -1,88 +1,401 @@
-use crate::client::Client;
+use crate::client::{AddressPreference, Client, ClientResult};
 use omgpp_core::{
-    ffi::{EndpointFFI, ToFfi},
-    ConnectionState,
+    ffi::{slice_from_ffi_ptr, EndpointFFI, ToFfi},
+    ConnectionRealTimeStatus, ConnectionState, ProcessStats,
 };
 use std::{
+    cell::Cell,
     ffi::{c_char, c_uchar, CStr},
     net::IpAddr,
+    panic::{self, AssertUnwindSafe},
     ptr::null_mut,
     str::FromStr,
+    time::Duration,
 };
 
 // FFI
 type ClientOnConnectionChanged = extern "C" fn(EndpointFFI, ConnectionState);
-type ClientOnMessage = extern "C" fn(EndpointFFI, i64, *const c_uchar, usize);
+type ClientOnMessage = extern "C" fn(EndpointFFI, bool, i64, *const c_uchar, usize);
 type ClientOnRpc = extern "C" fn(EndpointFFI, bool, i64, u64, i64, *const c_uchar, usize);
+type ClientOnMessageAcked = extern "C" fn(u64);
 
-#[no_mangle]
-pub unsafe extern "C" fn client_create(ip: *const c_char, port: u16) -> *mut Client {
-    let c_string = CStr::from_ptr(ip).to_str();
-    if c_string.is_err() {
-        return null_mut();
+/// Returned by every client FFI function, `client_create*` report it through `client_last_error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum ClientFfiError {
+    None = 0,
+    NullPointer = 1,
+    InvalidEncoding = 2, // string is not valid UTF-8
+    InvalidAddress = 3,  // string is not an IP address
+    ResolveFailed = 4,   // hostname cannot be resolved
+    ConnectFailed = 5,
+    SendFailed = 6,
+    ProcessFailed = 7,   // client is not connected or socket operation failed
+    Panicked = 8,        // e.g. a callback called back into the client while it was busy; destroy the client
+}
+
+thread_local! {
+    static LAST_ERROR: Cell<ClientFfiError> = const { Cell::new(ClientFfiError::None) };
+}
+// sets and returns the last error
+fn set_last_error(error: ClientFfiError) -> ClientFfiError {
+    LAST_ERROR.with(|last_error| last_error.set(error));
+    error
+}
+// maps the result to `error` if the call failed
+fn report_result<T>(result: ClientResult<T>, error: ClientFfiError) -> ClientFfiError {
+    match result {
+        Ok(_) => set_last_error(ClientFfiError::None),
+        Err(_) => set_last_error(error),
     }
+}
 
-    if let Some(addres) = IpAddr::from_str(c_string.unwrap()).ok() {
-        let client = Client::new(addres, port);
-        Box::into_raw(Box::from(client))
-    } else {
+// every FFI function runs its body through it, a panic must not unwind into the caller
+fn catch_panic(body: impl FnOnce() -> ClientFfiError) -> ClientFfiError {
+    panic::catch_unwind(AssertUnwindSafe(body))
+        .unwrap_or_else(|_panic| set_last_error(ClientFfiError::Panicked))
+}
+// same as `catch_panic` for functions creating the client
+fn catch_create_panic(body: impl FnOnce() -> *mut Client) -> *mut Client {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|_panic| {
+        set_last_error(ClientFfiError::Panicked);
         null_mut()
-    }
+    })
+}
+
+/// Error of the last call made on the current thread, e.g. why `client_create` returned null
+#[no_mangle]
+pub extern "C" fn client_last_error() -> ClientFfiError {
+    LAST_ERROR.with(|last_error| last_error.get())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn client_create(ip: *const c_char, port: u16) -> *mut Client {
+    catch_create_panic(|| {
+        if ip.is_null() {
+            set_last_error(ClientFfiError::NullPointer);
+            return null_mut();
+        }
+        let Ok(c_string) = CStr::from_ptr(ip).to_str() else {
+            set_last_error(ClientFfiError::InvalidEncoding);
+            return null_mut();
+        };
+
+        if let Some(addres) = IpAddr::from_str(c_string).ok() {
+            set_last_error(ClientFfiError::None);
+            let client = Client::new(addres, port);
+            Box::into_raw(Box::from(client))
+        } else {
+            set_last_error(ClientFfiError::InvalidAddress);
+            null_mut()
+        }
+    })
+}
+/// Same as `client_create` but resolves the hostname (blocking)
+#[no_mangle]
+pub unsafe extern "C" fn client_create_hostname(
+    host: *const c_char,
+    port: u16,
+    preference: AddressPreference,
+) -> *mut Client {
+    catch_create_panic(|| {
+        if host.is_null() {
+            set_last_error(ClientFfiError::NullPointer);
+            return null_mut();
+        }
+        let Ok(host) = CStr::from_ptr(host).to_str() else {
+            set_last_error(ClientFfiError::InvalidEncoding);
+            return null_mut();
+        };
+        match Client::from_hostname(host, port, preference) {
+            Ok(client) => {
+                set_last_error(ClientFfiError::None);
+                Box::into_raw(Box::from(client))
+            }
+            Err(_) => {
+                set_last_error(ClientFfiError::ResolveFailed);
+                null_mut()
+            }
+        }
+    })
+}
+
+/// Fails with `ProcessFailed` if the client is not connected.
+/// `out_stats` (may be null) receives number of handled events and messages,
+/// call again while any of them reaches 128 to drain the queue
+#[no_mangle]
+pub unsafe extern "C" fn client_process(
+    client: *mut Client,
+    out_stats: *mut ProcessStats,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_mut() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        let result = client.process::<128>();
+        if let (Ok(stats), false) = (&result, out_stats.is_null()) {
+            *out_stats = *stats;
+        }
+        report_result(result, ClientFfiError::ProcessFailed)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_connect(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_mut() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        report_result(client.connect(), ClientFfiError::ConnectFailed)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_disconnect(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.disconnect();
+        set_last_error(ClientFfiError::None)
+    })
+}
+/// Closes the current connection and retargets the client, `client_connect` has to be called afterwards
+#[no_mangle]
+pub unsafe extern "C" fn client_set_target(
+    client: *mut Client,
+    ip: *const c_char,
+    port: u16,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_mut() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        if ip.is_null() {
+            return set_last_error(ClientFfiError::NullPointer);
+        }
+        let Ok(c_string) = CStr::from_ptr(ip).to_str() else {
+            return set_last_error(ClientFfiError::InvalidEncoding);
+        };
+        let Ok(address) = IpAddr::from_str(c_string) else {
+            return set_last_error(ClientFfiError::InvalidAddress);
+        };
+        client.set_target(address, port);
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_set_reconnect_policy(
+    client: *mut Client,
+    max_retries: u32,
+    base_delay_ms: u64,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.set_reconnect_policy(max_retries, Duration::from_millis(base_delay_ms));
+        set_last_error(ClientFfiError::None)
+    })
+}
+/// The next connection starts a new session instead of resuming the current one
+#[no_mangle]
+pub unsafe extern "C" fn client_clear_session(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.set_session_token(None);
+        set_last_error(ClientFfiError::None)
+    })
 }
 
+/// Current state of the connection, `Connected` only after authentication
 #[no_mangle]
-pub unsafe extern "C" fn client_process(client: *mut Client) {
-    _ = client.as_mut().unwrap().process::<128>();
+pub unsafe extern "C" fn client_state(
+    client: *mut Client,
+    out_state: *mut ConnectionState,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(out_state)) = (client.as_ref(), out_state.as_mut()) else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        *out_state = client.state();
+        set_last_error(ClientFfiError::None)
+    })
 }
 #[no_mangle]
-pub unsafe extern "C" fn client_connect(client: *mut Client) {
-    client.as_mut().unwrap().connect().unwrap();
+pub unsafe extern "C" fn client_connection_status(
+    client: *mut Client,
+    out_status: *mut ConnectionRealTimeStatus,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(out_status)) = (client.as_ref(), out_status.as_mut()) else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        let result = client.connection_info();
+        if let Ok(status) = &result {
+            *out_status = *status;
+        }
+        report_result(result, ClientFfiError::ProcessFailed)
+    })
 }
 #[no_mangle]
-pub unsafe extern "C" fn client_disconnect(client: *mut Client) {
-    client.as_mut().unwrap().disconnect();
+pub unsafe extern "C" fn client_pending_bytes(
+    client: *mut Client,
+    out_bytes: *mut usize,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(out_bytes)) = (client.as_ref(), out_bytes.as_mut()) else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        let result = client.pending_bytes();
+        if let Ok(bytes) = &result {
+            *out_bytes = *bytes;
+        }
+        report_result(result, ClientFfiError::ProcessFailed)
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn client_register_on_connection_state_change(
     client: *mut Client,
     callback: ClientOnConnectionChanged,
-) {
-    client
-        .as_mut()
-        .unwrap()
-        .register_on_connection_state_changed(move |_client,endpoint, state| {
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.register_on_connection_state_changed(move |_client, endpoint, state| {
             callback(endpoint.to_ffi(), state)
         });
+        set_last_error(ClientFfiError::None)
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn client_register_on_message(
     client: *mut Client,
     callback: ClientOnMessage,
-) {
-    client
-        .as_mut()
-        .unwrap()
-        .register_on_message(move |_client,endpoint, message_id, data| {
-            callback(endpoint.to_ffi(), message_id, data.as_ptr(), data.len())
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.register_on_message(move |_client, endpoint, reliable, message_id, data| {
+            callback(endpoint.to_ffi(), reliable, message_id, data.as_ptr(), data.len())
         });
+        set_last_error(ClientFfiError::None)
+    })
 }
 #[no_mangle]
-pub unsafe extern "C" fn client_register_on_rpc(client: *mut Client, callback: ClientOnRpc) {
-    client.as_mut().unwrap().register_on_rpc(
-        move |_client,endpoint, reliable, method_id, request_id, arg_type, arg_data| {
-            callback(
-                endpoint.to_ffi(),
-                reliable,
-                method_id,
-                request_id,
-                arg_type,
-                arg_data.as_ptr(),
-                arg_data.len(),
-            )
-        },
-    );
+pub unsafe extern "C" fn client_register_on_rpc(
+    client: *mut Client,
+    callback: ClientOnRpc,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.register_on_rpc(
+            move |_client, endpoint, reliable, method_id, request_id, arg_type, arg_data| {
+                callback(
+                    endpoint.to_ffi(),
+                    reliable,
+                    method_id,
+                    request_id,
+                    arg_type,
+                    arg_data.as_ptr(),
+                    arg_data.len(),
+                )
+            },
+        );
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_register_on_message_acked(
+    client: *mut Client,
+    callback: ClientOnMessageAcked,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.register_on_message_acked(move |_client, message_number| callback(message_number));
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_unregister_on_message_acked(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.unregister_on_message_acked();
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_unregister_on_connection_state_change(
+    client: *mut Client,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.unregister_on_connection_state_changed();
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_unregister_on_message(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.unregister_on_message();
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_unregister_on_rpc(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.unregister_on_rpc();
+        set_last_error(ClientFfiError::None)
+    })
+}
+/// Messages of types not subscribed are dropped before `on_message` callback is crossed
+#[no_mangle]
+pub unsafe extern "C" fn client_subscribe(client: *mut Client, msg_type: i64) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.subscribe(msg_type);
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_unsubscribe(client: *mut Client, msg_type: i64) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.unsubscribe(msg_type);
+        set_last_error(ClientFfiError::None)
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn client_subscribe_all(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        let Some(client) = client.as_ref() else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        client.subscribe_all();
+        set_last_error(ClientFfiError::None)
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn client_send(
@@ -91,9 +404,14 @@ pub unsafe extern "C" fn client_send(
     data: *const c_uchar,
     offset: isize,
     size: usize,
-) {
-    let msg_data = core::slice::from_raw_parts(data.offset(offset), size);
-    _ = client.as_mut().unwrap().send(msg_type, msg_data)
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(msg_data)) = (client.as_ref(), slice_from_ffi_ptr(data, offset, size))
+        else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        report_result(client.send(msg_type, msg_data), ClientFfiError::SendFailed)
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn client_send_reliable(
@@ -102,9 +420,36 @@ pub unsafe extern "C" fn client_send_reliable(
     data: *const c_uchar,
     offset: isize,
     size: usize,
-) {
-    let msg_data = core::slice::from_raw_parts(data.offset(offset), size);
-    _ = client.as_mut().unwrap().send_reliable(msg_type, msg_data)
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(msg_data)) = (client.as_ref(), slice_from_ffi_ptr(data, offset, size))
+        else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        report_result(client.send_reliable(msg_type, msg_data), ClientFfiError::SendFailed)
+    })
+}
+/// Same as `client_send_reliable`, writes GNS message number later reported by `on_message_acked`
+#[no_mangle]
+pub unsafe extern "C" fn client_send_reliable_numbered(
+    client: *mut Client,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+    out_message_number: *mut u64,
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(msg_data)) = (client.as_ref(), slice_from_ffi_ptr(data, offset, size))
+        else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        let result = client.send_reliable(msg_type, msg_data);
+        if let (Ok(message_number), false) = (&result, out_message_number.is_null()) {
+            *out_message_number = *message_number;
+        }
+        report_result(result, ClientFfiError::SendFailed)
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn client_call_rpc(
@@ -116,24 +461,32 @@ pub unsafe extern "C" fn client_call_rpc(
     arg_data: *const c_uchar,
     arg_data_offset: isize,
     arg_data_size: usize,
-) {
-    let msg_data = match arg_data_size {
-        0 => None,
-        _ => Some(core::slice::from_raw_parts(arg_data.offset(arg_data_offset), arg_data_size)),
-    };
-    _ = client
-        .as_ref()
-        .unwrap()
-        .call_rpc(reliable, method_id, request_id, arg_type, msg_data);
+) -> ClientFfiError {
+    catch_panic(|| {
+        let (Some(client), Some(msg_data)) = (
+            client.as_ref(),
+            slice_from_ffi_ptr(arg_data, arg_data_offset, arg_data_size),
+        ) else {
+            return set_last_error(ClientFfiError::NullPointer);
+        };
+        let msg_data = match arg_data_size {
+            0 => None,
+            _ => Some(msg_data),
+        };
+        report_result(
+            client.call_rpc(reliable, method_id, request_id, arg_type, msg_data),
+            ClientFfiError::SendFailed,
+        )
+    })
 }
 
 #[no_mangle]
-#[allow(unreachable_patterns)]
-pub unsafe extern "C" fn client_destroy(client: *mut Client) {
-    match client.as_mut() {
-        client_ref => {
-            drop(client_ref);
+pub unsafe extern "C" fn client_destroy(client: *mut Client) -> ClientFfiError {
+    catch_panic(|| {
+        if client.is_null() {
+            return set_last_error(ClientFfiError::NullPointer);
         }
-        _ => (),
-    }
+        drop(Box::from_raw(client));
+        set_last_error(ClientFfiError::None)
+    })
 }
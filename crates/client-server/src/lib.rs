@@ -1,2 +1,4 @@
 pub mod client;
-pub mod server;
\ No newline at end of file
+pub mod server;
+#[cfg(feature = "test-util")]
+pub mod loopback;
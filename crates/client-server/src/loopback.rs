@@ -0,0 +1,60 @@
+//! Server and client living in the same process and driven by the caller, for tests.
+//! gns-rs doesn't expose GNS socket pairs, so the pair talks over a localhost port,
+//! but both sides are processed only by `pump`, in a deterministic order (server first).
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    thread,
+    time::{Duration, Instant},
+};
+
+use omgpp_core::ConnectionState;
+
+use crate::{
+    client::Client,
+    server::{server_error::ServerError, Server, ServerResult},
+};
+
+const PUMP_INTERVAL: Duration = Duration::from_millis(1);
+
+pub struct Loopback<'a> {
+    pub server: Server<'a>,
+    pub client: Client,
+}
+
+impl<'a> Loopback<'a> {
+    /// Binds the server to `127.0.0.1:port`, port `0` picks a free one so parallel tests don't collide.
+    /// The client is not connected yet, register callbacks first and call `connect`
+    pub fn new(port: u16) -> ServerResult<Loopback<'a>> {
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let server = Server::new(ip, port)?;
+        let client = Client::new(ip, server.local_addr().port());
+        Ok(Loopback { server, client })
+    }
+    /// Connects the client and pumps until it passes authentication
+    pub fn connect(&mut self, timeout: Duration) -> ServerResult<()> {
+        self.client.connect().or(Err(ServerError::Accept))?;
+        match self.pump_until(|loopback| loopback.client.state() == ConnectionState::Connected, timeout) {
+            true => Ok(()),
+            false => Err(ServerError::Accept),
+        }
+    }
+    /// Makes one cycle of the server and then of the client. Errors are dropped
+    pub fn pump(&mut self) {
+        _ = self.server.process::<128>();
+        _ = self.client.process::<128>();
+    }
+    /// Pumps until `condition` holds. Returns false if it didn't within `timeout`
+    pub fn pump_until(&mut self, condition: impl Fn(&Loopback) -> bool, timeout: Duration) -> bool {
+        let started_at = Instant::now();
+        loop {
+            self.pump();
+            if condition(self) {
+                return true;
+            }
+            if started_at.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(PUMP_INTERVAL);
+        }
+    }
+}
@@ -1,85 +1,320 @@
+pub mod callback_pool;
+pub mod connect_decision;
 pub mod connection_tracker;
+mod connection_transition;
+pub mod outgoing_limit;
+pub mod rate_limiter;
+pub mod send_outcome;
+pub mod server_builder;
+pub mod server_error;
+pub mod server_event;
+pub mod server_runner;
 pub mod server_settings;
+pub mod session_tracker;
+#[cfg(feature = "test-util")]
+pub mod synthetic_event;
 pub mod ffi;
 
-use std::cell::RefCell;
-use std::time::Duration;
-use std::{fmt::Debug, marker::PhantomData, net::IpAddr};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    net::{IpAddr, Ipv6Addr, SocketAddr, UdpSocket},
+};
 
-use connection_tracker::ConnectionTracker;
+use callback_pool::{CallbackPool, ServerTask, ServerTasks};
+use connect_decision::ConnectDecision;
+use connection_tracker::{ConnectionTracker, UuidScheme};
+use connection_transition::ConnectionTransition;
 
 use gns::ToReceive;
-use gns::{GnsConnectionEvent, GnsNetworkMessage, GnsSocket, IsCreated, IsServer};
+use gns::{GnsNetworkMessage, GnsSocket, IsCreated, IsServer};
+use gns::{GnsConfig, GnsConnection, GnsConnectionInfo};
 use gns_sys::{
     k_nSteamNetworkingSend_Reliable, k_nSteamNetworkingSend_Unreliable,
-    ESteamNetworkingConnectionState,
+    k_nSteamNetworkingSend_UnreliableNoDelay, k_nSteamNetworkingSend_UnreliableNoNagle, EResult,
+    ESteamNetworkingConfigValue, ESteamNetworkingConnectionState,
 };
 use omgpp_core::cmd_handler::{CmdHandler, CmdHandlerContainer};
-use omgpp_core::messages::general_message::general_omgpp_message::{self, *};
+use omgpp_core::messages::general_message::general_omgpp_message::*;
 use omgpp_core::{
     messages::general_message::GeneralOmgppMessage, ConnectionState, Endpoint, TransmitterHelper,
-    GNS,
+    gns, MAX_MESSAGE_SIZE,
+};
+use omgpp_core::compression::{decompress, Compression, MAX_DECOMPRESSED_MESSAGE_SIZE, MAX_DECOMPRESSED_SIZE};
+use omgpp_core::control_message::{
+    create_acked_message, create_cmd_message, create_flush_message, create_regular_message, create_rpc_message, ControlMessage,
+};
+use omgpp_core::ack::AckSender;
+use omgpp_core::dedup::DedupWindow;
+#[cfg(feature = "encryption")]
+use omgpp_core::encryption::{Encryption, KEY_SIZE};
+use omgpp_core::fragmentation::{create_fragment_messages, FragmentAssembler, MAX_FRAGMENTED_SIZE};
+use omgpp_core::rpc_registry::{RpcArgs, RpcRegistry};
+use omgpp_core::send_flags::SendFlags;
+use omgpp_core::typed_message::TypedMessage;
+use omgpp_core::{
+    ConnectionRealTimeStatus, DisconnectReason, LaneConfig, OmgppPredefinedCmd, ProcessStats, TrafficStats, TypeStats,
+    ToConnectionStatus, ToEndpoint,
 };
-use omgpp_core::{OmgppPredefinedCmd, ToEndpoint};
+use outgoing_limit::{OutgoingLimit, OverflowPolicy};
 use protobuf::Message;
-use server_settings::ServerSettings;
+use rate_limiter::{RateLimit, RateLimitPolicy, RateLimiter};
+use send_outcome::SendOutcome;
+use server_builder::ServerBuilder;
+use server_error::ServerError;
+use server_event::{ServerEvent, ServerEventKind};
+use server_runner::ServerHandle;
+use server_settings::{ListenFamily, PollOrder, QualityThresholds, ServerSettings};
+use session_tracker::SessionTracker;
 use uuid::Uuid;
 
-type OnConnectRequestCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint) -> bool + 'static>;
+type OnConnectRequestCallback =
+    Box<dyn Fn(&Server, &Uuid, &Endpoint, &GnsConnectionInfo) -> ConnectDecision + 'static>;
 type OnConnectionChangedCallback =
-Box<dyn Fn(&Server, &Uuid, &Endpoint, ConnectionState) + 'static>;
-type OnMessageCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint, i64, Vec<u8>) + 'static>;
+Box<dyn Fn(&Server, &Uuid, &Endpoint, ConnectionState, Option<&DisconnectReason>) + 'static>;
+type OnMessageCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint, bool, i64, Vec<u8>) + 'static>;
+// shared by the workers of the callback pool
+type OnThreadedMessageCallback = Arc<dyn Fn(&ServerTasks, &Uuid, &Endpoint, bool, i64, Vec<u8>) + Send + Sync + 'static>;
 type OnRpcCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint, bool, i64, u64, i64, Vec<u8>) + 'static>;
+type OnUnroutedMessageCallback = Box<dyn Fn(&Server, &GnsConnection, Vec<u8>) + 'static>;
+type OnPongCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint, Duration) + 'static>;
+// receives args of the auth command sent by the client
+type OnConnectionQualityChangedCallback =
+    Box<dyn Fn(&Server, &Uuid, &Endpoint, bool, &ConnectionRealTimeStatus) + 'static>;
+// FnMut, so per-tick game state can live in the closure
+type OnTickCallback = Box<dyn FnMut(&Server) + 'static>;
+type UuidProvider = Box<dyn Fn(&GnsConnectionInfo) -> Uuid + 'static>;
+type OnAuthenticateCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint, &[String]) -> bool + 'static>;
+// decodes the message and invokes the typed callback
+type OnTypedMessageCallback = Box<dyn Fn(&Server, &Uuid, &Endpoint, &[u8]) + 'static>;
+// returns false to drop the message
+type InboundFilter = Box<dyn Fn(&Uuid, i64, &[u8]) -> bool + 'static>;
+type OutboundTransform = Box<dyn Fn(&[u8]) -> Vec<u8> + 'static>;
 
-type ServerResult<T> = Result<T, String>; // TODO replace error with enum
+pub type ServerResult<T> = Result<T, ServerError>;
+
+// watch all possible reasons in ESteamNetConnectionEnd at steamworks_sdk_160\sdk\public\steam\steamnetworkingtypes.h (SteamworksSDK)
+const END_REASON_APP_GENERIC: i32 = 1000; // k_ESteamNetConnectionEnd_App_Generic
+const END_REASON_MISC_TIMEOUT: i32 = 5002; // k_ESteamNetConnectionEnd_Misc_Timeout
+// GNS default of `k_ESteamNetworkingConfig_TimeoutConnected`
+const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+// a few round trips of a decent connection
+const DEFAULT_ACK_RESEND_TIMEOUT: Duration = Duration::from_millis(100);
+// unacked messages are given up on afterwards, state updates are stale by then anyway
+const MAX_ACK_RETRANSMITS: u32 = 5;
 
 
 struct ServerCallbacks {
     on_connect_requested_callback: OnConnectRequestCallback,
     on_connection_changed_callback: Option<OnConnectionChangedCallback>,
     on_message_callback: Option<OnMessageCallback>,
+    on_threaded_message_callback: Option<OnThreadedMessageCallback>,
     on_rpc_callback: Option<OnRpcCallback>,
+    on_unrouted_message_callback: Option<OnUnroutedMessageCallback>,
+    on_pong_callback: Option<OnPongCallback>,
+    on_authenticate_callback: Option<OnAuthenticateCallback>,
+    uuid_provider: Option<UuidProvider>,
+    on_connection_quality_changed_callback: Option<OnConnectionQualityChangedCallback>,
+    on_tick_callback: Option<OnTickCallback>,
+    on_typed_message_callbacks: HashMap<i64, OnTypedMessageCallback>,
+    inbound_filters: Vec<InboundFilter>,
+    outbound_transforms: Vec<OutboundTransform>,
+}
+// recipients of a regular message. Broadcasts iterate active connections of the tracker lazily
+// instead of collecting them, see `send_to_targets_on_lane`
+enum Targets<'f> {
+    Clients(Vec<(Uuid, GnsConnection)>),
+    Active(&'f dyn Fn(&Uuid) -> bool),
 }
+
 pub struct Server<'a> {
-    ip: IpAddr,
-    port: u16,
+    // addresses the listeners are actually bound to, by listener index
+    local_addrs: Vec<SocketAddr>,
     connection_tracker: RefCell<ConnectionTracker>,
-    settings:ServerSettings,
-    socket: GnsSocket<'static, 'static, IsServer>,
+    settings: RefCell<ServerSettings>,
+    sockets: Vec<GnsSocket<'static, 'static, IsServer>>,
     callbacks: RefCell<ServerCallbacks>,
+    player_data: RefCell<HashMap<Uuid, Box<dyn Any>>>,
+    fragments: RefCell<HashMap<Uuid, FragmentAssembler>>,
+    next_fragmented_message_id: Cell<u64>,
+    pending_pings: RefCell<HashMap<(Uuid, u64), Instant>>,
+    // messages queued while batching is enabled: (client, connection, flags, lane, priority, encoded message)
+    pending_batch: RefCell<Vec<(Uuid, GnsConnection, i32, u16, u8, Vec<u8>)>>,
+    traffic_stats: RefCell<HashMap<Uuid, TrafficStats>>,
+    type_stats: RefCell<HashMap<i64, TypeStats>>,
+    // when the client sent its last message, or connected if nothing was received yet
+    last_received_at: RefCell<HashMap<Uuid, Instant>>,
+    // clients for which `on_connect_requested` returned `ConnectDecision::Pending`
+    pending_connects: RefCell<HashSet<Uuid>>,
+    rate_limiter: RefCell<RateLimiter>,
+    // per client overrides of `ServerSettings::outgoing_limit`
+    outgoing_limits: RefCell<HashMap<Uuid, OutgoingLimit>>,
+    sessions: RefCell<SessionTracker>,
+    dedup_windows: RefCell<HashMap<Uuid, DedupWindow>>,
+    // runs threaded message callbacks, None runs them on the polling thread
+    callback_pool: RefCell<Option<CallbackPool>>,
+    // tasks posted by threaded callbacks through `ServerTasks`
+    tasks: ServerTasks,
+    pending_tasks: Receiver<ServerTask>,
+    // messages sent with `send_unreliable_acked` waiting for the client's ack
+    ack_senders: RefCell<HashMap<Uuid, AckSender>>,
+    #[cfg(feature = "encryption")]
+    encryption: RefCell<Option<Encryption>>,
+    // clients whose connection currently exceeds `QualityThresholds`
+    poor_connections: RefCell<HashSet<Uuid>>,
+    last_quality_sample_at: Cell<Option<Instant>>,
+    // set while `poll_into` runs, events are collected here instead of invoking callbacks
+    event_queue: RefCell<Option<Vec<ServerEvent>>>,
+    next_ping_id: Cell<u64>,
     cmd_handlers: RefCell<CmdHandlerContainer<Server<'a>>>,
+    rpc_registry: RefCell<RpcRegistry<Server<'a>>>,
+    // disconnect events of listeners whose message queue was not drained yet: (listener, event)
+    deferred_disconnects: RefCell<Vec<(usize, ConnectionTransition)>>,
     phantom: PhantomData<&'a bool>,
 }
 
 impl<'a> Server<'a> {
+    /// Listens on `ip:port`. The address selects the interfaces clients can connect through:
+    /// `127.0.0.1` (or `::1`) accepts local clients only, e.g. for tests, `0.0.0.0` accepts clients of every
+    /// IPv4 interface and an address of a particular interface accepts clients of that interface only.
+    /// IPv4 addresses are bound as IPv4 sockets, so a server on `127.0.0.1` is not reachable on `::1`,
+    /// see `ListenFamily` to accept both. IPv4-mapped IPv6 addresses are treated as IPv4 ones.
+    /// Multicast, broadcast and link-local IPv6 addresses are rejected with `ServerError::UnsupportedListenAddress`.
+    /// Port `0` binds a free port chosen by the OS, see `local_addr`
     pub fn new(ip: IpAddr, port: u16) -> ServerResult<Server<'a>> {
-        let gns = GNS.as_ref()?;
-        let gns_socket = GnsSocket::<IsCreated>::new(&gns.global, &gns.utils).unwrap();
-        let address_to_bind = match ip {
-            IpAddr::V4(v4) => v4.to_ipv6_mapped(),
-            IpAddr::V6(v6) => v6,
-        };
-        let server_socket = gns_socket
-            .listen(address_to_bind, port)
-            .or(ServerResult::Err("Cannot create server socket".to_string()))?;
+        Server::new_with_family(ip, port, ListenFamily::Auto)
+    }
+    pub fn new_with_family(ip: IpAddr, port: u16, family: ListenFamily) -> ServerResult<Server<'a>> {
+        let (server_socket, local_addr) = Server::listen(ip, port, family)?;
+        let (tasks, pending_tasks) = ServerTasks::channel();
         let server = Server {
-            ip,
-            port,
-            socket: server_socket,
+            local_addrs: vec![local_addr],
+            sockets: vec![server_socket],
             connection_tracker: RefCell::new(ConnectionTracker::new(Duration::from_secs(3))),
-            settings:Default::default(),
+            settings: Default::default(),
             callbacks: RefCell::new(ServerCallbacks {
-                on_connect_requested_callback: Box::new(|_server, _id, _endpoint| true),
+                on_connect_requested_callback: Box::new(|_server, _id, _endpoint, _info| ConnectDecision::Accept),
                 on_connection_changed_callback: None,
                 on_message_callback: None,
+                on_threaded_message_callback: None,
                 on_rpc_callback: None,
+                on_unrouted_message_callback: None,
+                on_pong_callback: None,
+                on_authenticate_callback: None,
+                uuid_provider: None,
+                on_connection_quality_changed_callback: None,
+                on_tick_callback: None,
+                on_typed_message_callbacks: HashMap::new(),
+                inbound_filters: Vec::new(),
+                outbound_transforms: Vec::new(),
             }),
+            player_data: Default::default(),
+            fragments: Default::default(),
+            next_fragmented_message_id: Cell::new(0),
+            pending_pings: Default::default(),
+            event_queue: Default::default(),
+            pending_batch: Default::default(),
+            traffic_stats: Default::default(),
+            type_stats: Default::default(),
+            last_received_at: Default::default(),
+            pending_connects: Default::default(),
+            rate_limiter: Default::default(),
+            outgoing_limits: Default::default(),
+            sessions: Default::default(),
+            dedup_windows: Default::default(),
+            callback_pool: Default::default(),
+            tasks,
+            pending_tasks,
+            ack_senders: Default::default(),
+            #[cfg(feature = "encryption")]
+            encryption: RefCell::new(None),
+            poor_connections: Default::default(),
+            last_quality_sample_at: Cell::new(None),
+            next_ping_id: Cell::new(0),
             cmd_handlers: RefCell::new(CmdHandlerContainer::new()),
+            rpc_registry: RefCell::new(RpcRegistry::new()),
+            deferred_disconnects: Default::default(),
             phantom: Default::default(),
         };
         server.init_default_cmd_handlers();
         Ok(server)
     }
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+    /// Starts the server on a background thread, see `ServerBuilder::spawn`
+    pub fn run(ip: IpAddr, port: u16, tick_hz: u32) -> ServerResult<ServerHandle> {
+        ServerBuilder::new().bind(ip, port).spawn(tick_hz)
+    }
+    /// Starts listening on one more address. Returns index of the listener which is reported by `listener_of`
+    pub fn add_listener(&mut self, ip: IpAddr, port: u16) -> ServerResult<usize> {
+        let (server_socket, local_addr) = Server::listen(ip, port, ListenFamily::Auto)?;
+        self.sockets.push(server_socket);
+        self.local_addrs.push(local_addr);
+        Ok(self.sockets.len() - 1)
+    }
+    fn to_ipv6_mapped(ip: IpAddr) -> IpAddr {
+        match ip {
+            IpAddr::V4(v4) => IpAddr::V6(v4.to_ipv6_mapped()),
+            IpAddr::V6(_) => ip,
+        }
+    }
+    fn listen(
+        ip: IpAddr,
+        port: u16,
+        family: ListenFamily,
+    ) -> ServerResult<(GnsSocket<'static, 'static, IsServer>, SocketAddr)> {
+        let ip = ip.to_canonical();
+        if !Server::is_listen_address_supported(ip) {
+            return Err(ServerError::UnsupportedListenAddress(ip));
+        }
+        let gns = gns().map_err(ServerError::GnsInit)?;
+        let gns_socket =
+            GnsSocket::<IsCreated>::new(&gns.global, &gns.utils).ok_or(ServerError::SocketCreate)?;
+        let address_to_bind = match (family, ip) {
+            (ListenFamily::Auto | ListenFamily::Ipv4, IpAddr::V4(v4)) => v4.to_ipv6_mapped(),
+            (ListenFamily::Auto, IpAddr::V6(v6)) => v6,
+            (ListenFamily::Ipv4, IpAddr::V6(_)) => return Err(ServerError::Listen),
+            (ListenFamily::DualStack, IpAddr::V4(v4)) if v4.is_unspecified() => Ipv6Addr::UNSPECIFIED,
+            (ListenFamily::DualStack, IpAddr::V6(v6)) => v6,
+            (ListenFamily::DualStack, IpAddr::V4(_)) => return Err(ServerError::Listen),
+        };
+        let bound_ip = match address_to_bind.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(address_to_bind),
+        };
+        let port = Server::resolve_port(SocketAddr::new(bound_ip, port))?;
+        let server_socket = gns_socket
+            .listen(address_to_bind, port)
+            .or(ServerResult::Err(ServerError::Listen))?;
+        Ok((server_socket, SocketAddr::new(bound_ip, port)))
+    }
+    // GNS doesn't report the port the OS assigned to its socket, so for port `0` a free port is picked
+    // by binding a probe socket first. Another process may take it before GNS binds, which fails with `ServerError::Listen`
+    fn resolve_port(address: SocketAddr) -> ServerResult<u16> {
+        if address.port() != 0 {
+            return Ok(address.port());
+        }
+        let probe = UdpSocket::bind(address).or(Err(ServerError::Listen))?;
+        let probe_addr = probe.local_addr().or(Err(ServerError::Listen))?;
+        Ok(probe_addr.port())
+    }
+    // GNS listens on unicast addresses only. Link-local IPv6 addresses need a scope id to select
+    // the interface, which neither `IpAddr` nor GNS addresses carry
+    fn is_listen_address_supported(ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(v4) => !v4.is_multicast() && !v4.is_broadcast(),
+            IpAddr::V6(v6) => !v6.is_multicast() && (v6.segments()[0] & 0xffc0) != 0xfe80,
+        }
+    }
     fn init_default_cmd_handlers(&self) {
         let mut cmd_handlers = self.cmd_handlers.borrow_mut();
         _ = cmd_handlers.register_handler(CmdHandler::new(
@@ -87,7 +322,14 @@ impl<'a> Server<'a> {
             false,
             Box::new(Server::cmd_auth_handle),
         ));
+        _ = cmd_handlers.register_handler(CmdHandler::new(
+            OmgppPredefinedCmd::RESUME,
+            false,
+            Box::new(Server::cmd_resume_handle),
+        ));
         _ = cmd_handlers.register_handler(CmdHandler::new(OmgppPredefinedCmd::RESOURCES, false, Box::new(Server::cmd_resources_handle)));
+        _ = cmd_handlers.register_handler(CmdHandler::new(OmgppPredefinedCmd::PING, true, Box::new(Server::cmd_ping_handle)));
+        _ = cmd_handlers.register_handler(CmdHandler::new(OmgppPredefinedCmd::ACK, true, Box::new(Server::cmd_ack_handle)));
     }
     fn cmd_auth_handle(
         &self,
@@ -96,7 +338,14 @@ impl<'a> Server<'a> {
         _handler: &CmdHandler<Server>,
         request: &CmdRequest,
     ) {
-        let is_authenticated = true;
+        // repeated auth of an already verified client is ignored
+        if self.connection_tracker.borrow().state(uuid) != ConnectionState::ConnectedUnverified {
+            return;
+        }
+        let is_authenticated = match &self.callbacks.borrow().on_authenticate_callback {
+            Some(cb) => cb(self, uuid, endpoint, request.args.as_slice()),
+            None => true,
+        };
         let connection = self.connection_tracker.borrow().client_connection(uuid);
         if is_authenticated {
             if let Some(gns_connection) = connection {
@@ -105,23 +354,98 @@ impl<'a> Server<'a> {
                     endpoint.clone(),
                     gns_connection,
                 );
-                let new_state = self.connection_tracker.borrow().state(uuid);
-                let callbacks = self.callbacks.borrow();
-                if let Some(cb) = &callbacks.on_connection_changed_callback {
-                    cb(self, uuid, endpoint, new_state);
-                }
+                let (token, resumed) = self.sessions.borrow_mut().open(uuid);
+                let new_state = match resumed {
+                    true => ConnectionState::Reconnected,
+                    false => self.connection_tracker.borrow().state(uuid),
+                };
+                self.notify_connection_changed(uuid, endpoint, new_state, None);
+                // the token lets the client resume this session after reconnecting
                 _ = self.send_command(
                     uuid,
                     request.cmd.clone(),
                     request.request_id,
-                    Some(vec!["ok".to_string()]),
+                    Some(vec!["ok".to_string(), token]),
                 );
             }
         } else {
+            #[cfg(feature = "tracing")]
+            tracing::info!(client = %uuid, "authentication failed");
             if let Some(gns_connection) = connection {
-                self.socket.close_connection(gns_connection, 0, "", false);
+                self.socket().close_connection(
+                    gns_connection,
+                    END_REASON_APP_GENERIC as _,
+                    "Authentication failed",
+                    false,
+                );
+            }
+        }
+    }
+    // the connection takes over the uuid of the client's previous session.
+    // Its own uuid reported so far is dropped with `Disconnected` state
+    fn cmd_resume_handle(
+        &self,
+        uuid: &Uuid,
+        endpoint: &Endpoint,
+        _handler: &CmdHandler<Server>,
+        request: &CmdRequest,
+    ) {
+        if self.connection_tracker.borrow().state(uuid) != ConnectionState::ConnectedUnverified {
+            return;
+        }
+        let previous = request
+            .args
+            .get(0)
+            .and_then(|token| self.sessions.borrow().client_by_token(token));
+        let Some(previous) = previous else {
+            #[cfg(feature = "tracing")]
+            tracing::info!(client = %uuid, "session not resumed: unknown or expired token");
+            return;
+        };
+        if previous != *uuid {
+            let (connection, listener) = {
+                let tracker = self.connection_tracker.borrow();
+                (tracker.client_connection(uuid), tracker.client_listener(uuid))
+            };
+            let (Some(connection), Some(listener)) = (connection, listener) else {
+                return;
+            };
+            // previous connection may be still alive if the server has not noticed it was lost yet
+            if self.connection_tracker.borrow().client_connection(&previous).is_some() {
+                _ = self.close_client(&previous, END_REASON_APP_GENERIC, "Session resumed", false);
+            }
+            self.track_client_disconnected(uuid);
+            self.notify_connection_changed(uuid, endpoint, ConnectionState::Disconnected, None);
+            {
+                let mut tracker = self.connection_tracker.borrow_mut();
+                tracker.track_client_connecting(previous.clone(), endpoint.clone(), connection.clone(), listener);
+                tracker.track_client_connected_unverified(previous.clone(), endpoint.clone(), connection);
             }
+            self.last_received_at
+                .borrow_mut()
+                .insert(previous.clone(), Instant::now());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::info!(client = %previous, "session resumed, waiting for auth");
+        self.sessions.borrow_mut().track_client_resumed(&previous);
+    }
+    // with `PollOrder::MessagesFirst` the first commands of the client (auth) may be handled before
+    // its `Connected` event. GNS delivers messages of established connections only, so the message proves it
+    fn track_connected_by_message(&self, uuid: &Uuid, endpoint: &Endpoint) {
+        if self.connection_tracker.borrow().state(uuid) != ConnectionState::Connecting
+            || self.pending_connects.borrow().contains(uuid)
+        {
+            return;
         }
+        let Some(connection) = self.connection_tracker.borrow().client_connection(uuid) else {
+            return;
+        };
+        self.connection_tracker.borrow_mut().track_client_connected_unverified(
+            uuid.clone(),
+            *endpoint,
+            connection,
+        );
+        self.notify_connection_changed(uuid, endpoint, ConnectionState::ConnectedUnverified, None);
     }
     fn cmd_resources_handle(
         &self,
@@ -130,59 +454,481 @@ impl<'a> Server<'a> {
         _handler: &CmdHandler<Server>,
         request: &CmdRequest,
     ) {
-        let resource_location = self.settings.resource_location.clone();
+        let resource_location = self.settings.borrow().resource_location.clone();
         _ = self.send_command(uuid, request.cmd.clone(), request.request_id, Some(vec![resource_location]))
     }
+    // client echoed the ping
+    fn cmd_ping_handle(
+        &self,
+        uuid: &Uuid,
+        endpoint: &Endpoint,
+        _handler: &CmdHandler<Server>,
+        request: &CmdRequest,
+    ) {
+        let sent_at = self
+            .pending_pings
+            .borrow_mut()
+            .remove(&(uuid.clone(), request.request_id));
+        if let (Some(sent_at), Some(cb)) = (sent_at, &self.callbacks.borrow().on_pong_callback) {
+            cb(self, uuid, endpoint, sent_at.elapsed());
+        }
+    }
+    // client acknowledged messages sent with `send_unreliable_acked`
+    fn cmd_ack_handle(
+        &self,
+        uuid: &Uuid,
+        _endpoint: &Endpoint,
+        _handler: &CmdHandler<Server>,
+        request: &CmdRequest,
+    ) {
+        let (Some(Ok(latest)), Some(Ok(bits))) = (
+            request.args.get(0).map(|latest| latest.parse::<u64>()),
+            request.args.get(1).map(|bits| bits.parse::<u32>()),
+        ) else {
+            return;
+        };
+        if let Some(sender) = self.ack_senders.borrow_mut().get_mut(uuid) {
+            sender.acknowledge(latest, bits);
+        }
+    }
     // TODO Maybe it worth to return a Iterator instead of cloning
     pub fn active_clients(&self) -> Vec<(Uuid, Endpoint)> {
         self.connection_tracker.borrow().active_clients()
     }
+    pub fn connected_players(&self) -> Vec<Uuid> {
+        self.connection_tracker
+            .borrow()
+            .active_client_connections()
+            .map(|(client, _connection)| client)
+            .collect()
+    }
+    pub fn is_connected(&self, client: &Uuid) -> bool {
+        self.connection_tracker.borrow().state(client) == ConnectionState::Connected
+    }
+    /// Returns ping, connection quality and throughput of the client connection
+    pub fn connection_info(&self, client: &Uuid) -> ServerResult<ConnectionRealTimeStatus> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        let (status, _lanes) = self
+            .socket()
+            .get_connection_real_time_status(connection, 0)
+            .or(Err(ServerError::ConnectionStatus))?;
+        Ok(status.to_connection_status())
+    }
+    /// Address the listener created in `Server::new` is bound to, with the port chosen by the OS
+    /// when bound to port `0`. IPv4 listeners report IPv4 addresses, a dual stack one on `0.0.0.0` reports `::`
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addrs[0]
+    }
+    /// Address the listener is bound to, see `local_addr`
+    pub fn listener_addr(&self, listener: usize) -> Option<SocketAddr> {
+        self.local_addrs.get(listener).cloned()
+    }
+    /// Index of the listener which accepted the client. `0` is the listener created in `Server::new`
+    pub fn listener_of(&self, client: &Uuid) -> Option<usize> {
+        self.connection_tracker.borrow().client_listener(client)
+    }
+    /// Remote address of the client's connection. IPv4 clients are reported with IPv4 addresses,
+    /// although GNS tracks them IPv6-mapped
+    pub fn remote_addr(&self, client: &Uuid) -> Option<SocketAddr> {
+        let endpoint = self.connection_tracker.borrow().client_endpoint(client).cloned()?;
+        let ip = match endpoint.ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(endpoint.ip, IpAddr::V4),
+            ip => ip,
+        };
+        Some(SocketAddr::new(ip, endpoint.port))
+    }
+    /// GNS handle of the client's connection, can be cached for `send_by_connection`
+    pub fn connection_of(&self, client: &Uuid) -> Option<GnsConnection> {
+        self.connection_tracker.borrow().client_connection(client)
+    }
+    /// Client the GNS connection belongs to, the reverse of `connection_of`.
+    /// `None` for connections which are not tracked (e.g. already closed)
+    pub fn player_of(&self, connection: GnsConnection) -> Option<Uuid> {
+        self.connection_tracker
+            .borrow()
+            .client_by_connection(&connection)
+            .cloned()
+    }
+    /// Socket of the listener created in `Server::new`
     pub fn socket(&self) -> &GnsSocket<'static, 'static, IsServer> {
-        &self.socket
+        &self.sockets[0]
     }
     /// Make 1 server cycle.
-    /// Generic paramter N specfies maximum number of events and messages to process per a call
-    pub fn process<const N: usize>(&self) -> ServerResult<()> {
-        let socket = &self.socket;
-        socket.poll_callbacks();
-        let mut socket_op_result = ServerResult::Ok(());
-        let _processed_event_count = socket.poll_event::<N>(|event| {
-            socket_op_result = Server::process_connection_events(
-                self,
-                event,
-                &self.socket,
-                &self.callbacks.borrow(),
-                &self.connection_tracker,
-            )
-        });
-
-        let _processed_msg_count = socket.poll_messages::<N>(|msg| {
-            socket_op_result = Server::process_messages(
-                self,
-                msg,
-                &self.connection_tracker,
-                &self.callbacks.borrow(),
-            )
-        });
+    /// Generic paramter N specfies maximum number of events and messages to process per a call per listener
+    /// All events and messages are processed even if some of them fail. A single failure is returned as is,
+    /// several failures are returned as `ServerError::Process`.
+    /// Disconnects are reported after messages the client sent before disconnecting
+    pub fn process<const N: usize>(&self) -> ServerResult<ProcessStats> {
+        let result = self.process_pass::<N>();
+        self.notify_tick();
+        result
+    }
+    // a single cycle of `process` without `on_tick`, so callers polling several passes tick once
+    fn process_pass<const N: usize>(&self) -> ServerResult<ProcessStats> {
+        let mut errors = Vec::new();
+        let mut stats = ProcessStats::default();
+        while let Ok(task) = self.pending_tasks.try_recv() {
+            task(self);
+        }
+        for (listener, socket) in self.sockets.iter().enumerate() {
+            socket.poll_callbacks();
+            let deferred = std::mem::take(&mut *self.deferred_disconnects.borrow_mut());
+            let (own_deferred, other_deferred): (Vec<_>, Vec<_>) = deferred
+                .into_iter()
+                .partition(|(event_listener, _event)| *event_listener == listener);
+            *self.deferred_disconnects.borrow_mut() = other_deferred;
+            let mut disconnects: Vec<ConnectionTransition> =
+                own_deferred.into_iter().map(|(_listener, transition)| transition).collect();
+            let poll_order = self.settings.borrow().poll_order;
+            if poll_order == PollOrder::EventsFirst {
+                self.poll_listener_events::<N>(listener, socket, &mut stats, &mut errors, &mut disconnects);
+            }
+            let messages = self.poll_listener_messages::<N>(socket, &mut stats, &mut errors);
+            if poll_order == PollOrder::MessagesFirst {
+                self.poll_listener_events::<N>(listener, socket, &mut stats, &mut errors, &mut disconnects);
+            }
+            // the message queue may be not drained yet, disconnects wait for the next call
+            if messages >= N {
+                self.deferred_disconnects
+                    .borrow_mut()
+                    .extend(disconnects.into_iter().map(|transition| (listener, transition)));
+                continue;
+            }
+            for transition in disconnects {
+                if let Err(err) = Server::process_connection_events(
+                    self,
+                    transition,
+                    listener,
+                    socket,
+                    &self.callbacks.borrow(),
+                    &self.connection_tracker,
+                ) {
+                    errors.push(err);
+                }
+            }
+        }
 
+        let socket = self.socket();
         let connection_tracker = self.connection_tracker.borrow();
         let expired_unverified_connections = connection_tracker
             .expired_unverified_connections()
             .enumerate();
         for (_i, connection) in expired_unverified_connections {
-            println!("{:?}", connection);
-            socket.close_connection(connection, 0, "Unverified", false);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(?connection, "authentication timed out, closing connection");
+            socket.close_connection(
+                connection,
+                END_REASON_APP_GENERIC as _,
+                "Authentication timed out",
+                false,
+            );
         }
+        drop(connection_tracker);
+        self.retransmit_unacked();
+        self.sample_connection_quality();
+        // failures are reported only by explicit `flush`
+        _ = self.flush();
 
-        socket_op_result
+        match errors.len() {
+            0 => Ok(stats),
+            1 => Err(errors.remove(0)),
+            _ => Err(ServerError::Process(errors)),
+        }
+    }
+    /// Same as `process` but connection changes, messages and rpc calls are appended to `events`
+    /// instead of invoking the registered callbacks. Only `Connected`/`Disconnected` transitions are reported.
+    /// `on_connect_requested` is still invoked to decide whether to accept the client
+    pub fn poll_into<const N: usize>(
+        &self,
+        events: &mut Vec<ServerEvent>,
+    ) -> ServerResult<ProcessStats> {
+        self.with_event_queue(events, || {
+            let result = self.process_pass::<N>();
+            self.notify_tick();
+            result
+        })
+    }
+    /// Same as `poll_into` but returns a new vector. Errors are dropped, use `poll_into` to get them
+    pub fn poll_events<const N: usize>(&self) -> Vec<ServerEvent> {
+        let mut events = Vec::new();
+        _ = self.poll_into::<N>(&mut events);
+        events
+    }
+    /// Same as `poll_events` but polls until GNS has no more queued events and messages,
+    /// so the chunk size doesn't have to be picked at compile time. Errors are dropped, use `drain_into` to get them
+    pub fn drain_events(&self) -> impl Iterator<Item = ServerEvent> {
+        let mut events = Vec::new();
+        _ = self.drain_into(&mut events);
+        events.into_iter()
+    }
+    /// Same as `poll_into` but polls until GNS has no more queued events and messages.
+    /// Stops at the first failed pass and returns its error, events handled so far are kept in `events`.
+    /// `on_tick` is invoked once, after the last pass
+    pub fn drain_into(&self, events: &mut Vec<ServerEvent>) -> ServerResult<ProcessStats> {
+        const DRAIN_CHUNK: usize = 64;
+        let drain = || -> ServerResult<ProcessStats> {
+            let mut total = ProcessStats::default();
+            loop {
+                let stats = self.process_pass::<DRAIN_CHUNK>()?;
+                total.events += stats.events;
+                total.messages += stats.messages;
+                // a socket returns less than requested only when its queue is empty
+                if stats.events < DRAIN_CHUNK && stats.messages < DRAIN_CHUNK {
+                    return Ok(total);
+                }
+            }
+        };
+        self.with_event_queue(events, || {
+            let result = drain();
+            self.notify_tick();
+            result
+        })
     }
-    pub fn send(&self, client: &Uuid, msg_type: i64, data: &[u8]) -> ServerResult<()> {
-        self.send_with_flags(client, msg_type, data, k_nSteamNetworkingSend_Unreliable)
+    // connection changes, messages and rpc calls reported while `body` runs are appended to `events`
+    fn with_event_queue<R>(&self, events: &mut Vec<ServerEvent>, body: impl FnOnce() -> R) -> R {
+        *self.event_queue.borrow_mut() = Some(std::mem::take(events));
+        let result = body();
+        *events = self.event_queue.borrow_mut().take().unwrap_or_default();
+        result
+    }
+    /// Same as `process` but waits up to `timeout` until there is at least one event or message to handle.
+    /// gns-rs doesn't expose a wait primitive, so the sockets are polled with a short sleep in between.
+    /// `on_tick` is invoked once, after the last poll
+    pub fn process_blocking<const N: usize>(&self, timeout: Duration) -> ServerResult<ProcessStats> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(1);
+        let started_at = Instant::now();
+        let poll = || -> ServerResult<ProcessStats> {
+            loop {
+                let stats = self.process_pass::<N>()?;
+                let elapsed = started_at.elapsed();
+                if stats.events > 0 || stats.messages > 0 || elapsed >= timeout {
+                    return Ok(stats);
+                }
+                thread::sleep(POLL_INTERVAL.min(timeout - elapsed));
+            }
+        };
+        let result = poll();
+        self.notify_tick();
+        result
+    }
+    /// Sends unreliable message to the client.
+    /// `msg_type` is encoded into the payload and passed as is to the client's `on_message` callback.
+    /// Messages GNS could not queue are reported as `SendOutcome` instead of an error, so callers can track their drop rate
+    pub fn send(&self, client: &Uuid, msg_type: i64, data: &[u8]) -> ServerResult<SendOutcome> {
+        let failed =
+            self.try_send_with_flags(client, msg_type, data, k_nSteamNetworkingSend_Unreliable)?;
+        Ok(failed.map(SendOutcome::from).unwrap_or(SendOutcome::Queued))
     }
 
     pub fn send_reliable(&self, client: &Uuid, msg_type: i64, data: &[u8]) -> ServerResult<()> {
         self.send_with_flags(client, msg_type, data, k_nSteamNetworkingSend_Reliable)
     }
+    /// Same as `send_reliable`, but while batching is enabled (see `set_batching`) messages with higher `priority`
+    /// are submitted to GNS before lower priority ones queued since the last flush, regardless of the order they were sent in.
+    /// Messages sent by other methods have priority 0. Without batching messages are sent immediately and priority has no effect
+    pub fn send_reliable_with_priority(
+        &self,
+        client: &Uuid,
+        msg_type: i64,
+        data: &[u8],
+        priority: u8,
+    ) -> ServerResult<()> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        let failed = self.send_regular_message_on_lane(
+            Targets::Clients(vec![(client.clone(), connection)]),
+            k_nSteamNetworkingSend_Reliable,
+            0,
+            priority,
+            msg_type,
+            data,
+        )?;
+        match failed.into_iter().next() {
+            Some((_client, result)) => Err(ServerError::SendFailed(result)),
+            None => Ok(()),
+        }
+    }
+    /// Same as `send_reliable`. Reliable messages of a connection (lane) are delivered exactly once
+    /// and in the order they were sent, like over TCP
+    pub fn send_reliable_ordered(&self, client: &Uuid, msg_type: i64, data: &[u8]) -> ServerResult<()> {
+        self.send_reliable(client, msg_type, data)
+    }
+    /// Unreliable send which is never queued (`k_nSteamNetworkingSend_UnreliableNoDelay`): the message goes out
+    /// right away, without waiting for Nagle, or is dropped when it can't (reported as `SendOutcome::Dropped`).
+    /// It may be lost or delivered out of order relative to other messages, but never arrives late,
+    /// e.g. for positions superseded by the next update
+    pub fn send_unreliable_unordered(&self, client: &Uuid, msg_type: i64, data: &[u8]) -> ServerResult<SendOutcome> {
+        let failed = self.try_send_with_flags(
+            client,
+            msg_type,
+            data,
+            k_nSteamNetworkingSend_UnreliableNoDelay,
+        )?;
+        Ok(failed.map(SendOutcome::from).unwrap_or(SendOutcome::Queued))
+    }
+    /// Unreliable send which the client acknowledges. Messages not acknowledged within `set_ack_resend_timeout`
+    /// are sent again (up to 5 times), so losses are recovered without the head-of-line blocking of reliable messages.
+    /// The client drops duplicates and messages which arrive after more than 32 newer ones,
+    /// so delivery order is not guaranteed. Not fragmented, the message must fit into `MAX_MESSAGE_SIZE`
+    pub fn send_unreliable_acked(&self, client: &Uuid, msg_type: i64, data: &[u8]) -> ServerResult<SendOutcome> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        if !self.fits_outgoing_limit(client, data.len())? {
+            return Ok(SendOutcome::from(EResult::k_EResultLimitExceeded));
+        }
+        let (compression, payload, plain_size) = self.encode_payload(msg_type, data)?;
+        if plain_size > MAX_DECOMPRESSED_MESSAGE_SIZE {
+            return Err(ServerError::MessageTooLarge {
+                size: plain_size,
+                max: MAX_DECOMPRESSED_MESSAGE_SIZE,
+            });
+        }
+        let sequence = self
+            .ack_senders
+            .borrow_mut()
+            .entry(client.clone())
+            .or_default()
+            .next_sequence();
+        let msg_bytes = create_acked_message(msg_type, &payload, compression, sequence)
+            .or(Err(ServerError::MessageEncode))?;
+        let failed = self.send_to_clients(
+            vec![(client.clone(), connection)],
+            k_nSteamNetworkingSend_Unreliable,
+            &msg_bytes,
+        )?;
+        self.record_type_sent(msg_type, 1 - failed.len(), msg_bytes.len());
+        // a message GNS could not queue is recovered like a lost one
+        if let Some(sender) = self.ack_senders.borrow_mut().get_mut(client) {
+            sender.track_sent(sequence, msg_bytes);
+        }
+        Ok(failed
+            .into_iter()
+            .next()
+            .map(|(_client, result)| SendOutcome::from(result))
+            .unwrap_or(SendOutcome::Queued))
+    }
+    /// Time the client has to acknowledge a message sent with `send_unreliable_acked` before it is sent again.
+    /// Should be above the round-trip time of the connections, 100ms by default
+    pub fn set_ack_resend_timeout(&self, timeout: Duration) {
+        self.settings.borrow_mut().ack_resend_timeout = Some(timeout);
+    }
+    pub fn send_msg<M: TypedMessage>(&self, client: &Uuid, message: &M) -> ServerResult<SendOutcome> {
+        self.send(client, M::MSG_TYPE, &message.encode())
+    }
+    pub fn send_msg_reliable<M: TypedMessage>(&self, client: &Uuid, message: &M) -> ServerResult<()> {
+        self.send_reliable(client, M::MSG_TYPE, &message.encode())
+    }
+    /// Sends the message with arbitrary GNS flags, e.g. `SendFlags::UNRELIABLE | SendFlags::NO_NAGLE`
+    pub fn send_with(
+        &self,
+        client: &Uuid,
+        flags: SendFlags,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<()> {
+        self.send_with_flags(client, msg_type, data, flags.bits())
+    }
+    /// Configures priority lanes of the client's connection, e.g. to keep voice chat from waiting behind a file transfer.
+    /// Lane 0 is used by every send function except `send_on_lane`
+    pub fn configure_lanes(&self, client: &Uuid, lanes: &[LaneConfig]) -> ServerResult<()> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        let lanes = lanes
+            .iter()
+            .map(|lane| (lane.priority as _, lane.weight as _))
+            .collect::<Vec<_>>();
+        match self.socket().configure_connection_lanes(connection, &lanes) {
+            EResult::k_EResultOK => Ok(()),
+            result => Err(ServerError::LaneConfig(result)),
+        }
+    }
+    /// Sends the message on a lane configured with `configure_lanes`
+    pub fn send_on_lane(
+        &self,
+        client: &Uuid,
+        lane: u16,
+        reliable: bool,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<()> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        let flags = match reliable {
+            true => k_nSteamNetworkingSend_Reliable,
+            false => k_nSteamNetworkingSend_Unreliable,
+        };
+        let failed = self.send_regular_message_on_lane(
+            Targets::Clients(vec![(client.clone(), connection)]),
+            flags,
+            lane,
+            0,
+            msg_type,
+            data,
+        )?;
+        match failed.into_iter().next() {
+            Some((_client, result)) => Err(ServerError::SendFailed(result)),
+            None => Ok(()),
+        }
+    }
+    /// Sends the message to a connection handle obtained earlier, e.g. from `connection_of`.
+    /// Fails with `UnknownConnection` if the handle does not belong to a tracked client
+    pub fn send_by_connection(
+        &self,
+        connection: GnsConnection,
+        reliable: bool,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<()> {
+        let client = self
+            .connection_tracker
+            .borrow()
+            .client_by_connection(&connection)
+            .cloned()
+            .ok_or(ServerError::UnknownConnection)?;
+        let flags = match reliable {
+            true => k_nSteamNetworkingSend_Reliable,
+            false => k_nSteamNetworkingSend_Unreliable,
+        };
+        let failed = self.send_regular_message(Targets::Clients(vec![(client, connection)]), flags, msg_type, data)?;
+        match failed.into_iter().next() {
+            Some((_client, result)) => Err(ServerError::SendFailed(result)),
+            None => Ok(()),
+        }
+    }
+    /// Sends the message to the given subset of clients within a single batch.
+    /// Returns clients for which the message could not be sent
+    pub fn send_to_many(
+        &self,
+        clients: &[Uuid],
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.send_to_many_with_flags(clients, msg_type, data, k_nSteamNetworkingSend_Unreliable)
+    }
+    pub fn send_to_many_reliable(
+        &self,
+        clients: &[Uuid],
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.send_to_many_with_flags(clients, msg_type, data, k_nSteamNetworkingSend_Reliable)
+    }
     pub fn send_command(
         &self,
         client: &Uuid,
@@ -194,31 +940,95 @@ impl<'a> Server<'a> {
             .connection_tracker
             .borrow()
             .client_connection(client)
-            .ok_or_else(|| "There is not such client to send")?;
+            .ok_or(ServerError::UnknownPlayer)?;
 
         let cmd_bytes =
-            Self::create_cmd_message(cmd, request_id, args.unwrap_or_else(|| Vec::new()))
-                .or_else(|_or| Err("Cannot create cmd message".to_string()))?;
+            create_cmd_message(cmd, request_id, args.unwrap_or_else(|| Vec::new()))
+                .or(Err(ServerError::MessageEncode))?;
 
-        // TODO check send result
-        let _send_result = TransmitterHelper::send(
-            &self.socket,
-            &[connection],
+        self.send_to_client(
+            client,
+            connection,
             k_nSteamNetworkingSend_Reliable,
             cmd_bytes.as_slice(),
-        );
-        Ok(())
+        )
     }
-    pub fn broadcast(&self, msg_type: i64, data: &[u8]) -> ServerResult<()> {
-        let msg_bytes = Server::create_regular_message(msg_type, data)
-            .or_else(|_or| Err("Cannot create general message".to_string()))?;
-
-        self.broadcast_with_flags(k_nSteamNetworkingSend_Unreliable, msg_bytes.as_slice())
+    /// Sends the message to every verified client.
+    /// Returns clients for which the message could not be sent
+    pub fn broadcast(&self, msg_type: i64, data: &[u8]) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.send_regular_message(Targets::Active(&|_client| true), k_nSteamNetworkingSend_Unreliable, msg_type, data)
+    }
+    pub fn broadcast_reliable(
+        &self,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.send_regular_message(Targets::Active(&|_client| true), k_nSteamNetworkingSend_Reliable, msg_type, data)
+    }
+    /// Sends the message to every verified client except the given ones, e.g. relays client input to other clients
+    pub fn broadcast_except(
+        &self,
+        exclude: &[Uuid],
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let not_excluded = |client: &Uuid| !exclude.contains(client);
+        self.send_regular_message(Targets::Active(&not_excluded), k_nSteamNetworkingSend_Unreliable, msg_type, data)
+    }
+    pub fn broadcast_except_reliable(
+        &self,
+        exclude: &[Uuid],
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let not_excluded = |client: &Uuid| !exclude.contains(client);
+        self.send_regular_message(Targets::Active(&not_excluded), k_nSteamNetworkingSend_Reliable, msg_type, data)
     }
-    pub fn broadcast_reliable(&self, msg_type: i64, data: &[u8]) -> ServerResult<()> {
-        let msg_bytes = Server::create_regular_message(msg_type, data)
-            .or_else(|_or| Err("Cannot create general message".to_string()))?;
-        self.broadcast_with_flags(k_nSteamNetworkingSend_Reliable, msg_bytes.as_slice())
+    /// Broadcasts to active clients matching the predicate, e.g. players of a team stored in player data.
+    /// The predicate may access the server
+    pub fn broadcast_where(
+        &self,
+        pred: impl Fn(&Uuid) -> bool,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let targets = Targets::Clients(self.matching_targets(pred));
+        self.send_regular_message(targets, k_nSteamNetworkingSend_Unreliable, msg_type, data)
+    }
+    pub fn broadcast_where_reliable(
+        &self,
+        pred: impl Fn(&Uuid) -> bool,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let targets = Targets::Clients(self.matching_targets(pred));
+        self.send_regular_message(targets, k_nSteamNetworkingSend_Reliable, msg_type, data)
+    }
+    /// Broadcasts to every active client, `is_reliable` decides per client whether the message is sent reliably
+    /// (e.g. reliably to players near an event, unreliably to distant spectators). The predicate may access the server
+    pub fn broadcast_with_reliability(
+        &self,
+        is_reliable: impl Fn(&Uuid) -> bool,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let (reliable_targets, unreliable_targets): (Vec<_>, Vec<_>) = self
+            .active_targets(|_client| true)
+            .into_iter()
+            .partition(|(client, _connection)| is_reliable(client));
+        let mut failed = self.send_regular_message(
+            Targets::Clients(reliable_targets),
+            k_nSteamNetworkingSend_Reliable,
+            msg_type,
+            data,
+        )?;
+        failed.extend(self.send_regular_message(
+            Targets::Clients(unreliable_targets),
+            k_nSteamNetworkingSend_Unreliable,
+            msg_type,
+            data,
+        )?);
+        Ok(failed)
     }
     pub fn call_rpc(
         &self,
@@ -228,25 +1038,47 @@ impl<'a> Server<'a> {
         request_id: u64,
         arg_type: i64,
         arg_data: Option<&[u8]>,
+    ) -> ServerResult<()> {
+        self.send_rpc(client, reliable, method_id, request_id, arg_type, arg_data, false)
+    }
+    /// Replies to the client's `Client::call_rpc_with_reply`. `request_id` must be the one received in `on_rpc`
+    pub fn reply_rpc(
+        &self,
+        client: &Uuid,
+        reliable: bool,
+        method_id: i64,
+        request_id: u64,
+        arg_type: i64,
+        arg_data: Option<&[u8]>,
+    ) -> ServerResult<()> {
+        self.send_rpc(client, reliable, method_id, request_id, arg_type, arg_data, true)
+    }
+    fn send_rpc(
+        &self,
+        client: &Uuid,
+        reliable: bool,
+        method_id: i64,
+        request_id: u64,
+        arg_type: i64,
+        arg_data: Option<&[u8]>,
+        is_reply: bool,
     ) -> ServerResult<()> {
         let connection = self
             .connection_tracker
             .borrow()
             .client_connection(client)
-            .ok_or_else(|| "There is not such client to send")?;
+            .ok_or(ServerError::UnknownPlayer)?;
 
-        let msg_bytes =
-            Server::create_rpc_message(reliable, method_id, request_id, arg_type, arg_data)
-                .or_else(|_or| Err("Cannot create rpc message".to_string()))?;
+        let msg_bytes = create_rpc_message(
+            reliable, method_id, request_id, arg_type, arg_data, is_reply,
+        )
+        .or(Err(ServerError::MessageEncode))?;
 
         let flags = match reliable {
             true => k_nSteamNetworkingSend_Reliable,
             false => k_nSteamNetworkingSend_Unreliable,
         };
-        // TODO check send result
-        let _send_result =
-            TransmitterHelper::send(&self.socket, &[connection], flags, msg_bytes.as_slice());
-        Ok(())
+        self.send_to_client(client, connection, flags, msg_bytes.as_slice())
     }
     pub fn call_rpc_broadcast(
         &self,
@@ -255,97 +1087,833 @@ impl<'a> Server<'a> {
         request_id: u64,
         arg_type: i64,
         arg_data: Option<&[u8]>,
-    ) -> ServerResult<()> {
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
         let msg_bytes =
-            Server::create_rpc_message(reliable, method_id, request_id, arg_type, arg_data)
-                .or_else(|_or| Err("Cannot create rpc message".to_string()))?;
+            create_rpc_message(reliable, method_id, request_id, arg_type, arg_data, false)
+                .or(Err(ServerError::MessageEncode))?;
         let flags = match reliable {
             true => k_nSteamNetworkingSend_Reliable,
             false => k_nSteamNetworkingSend_Unreliable,
         };
-        let tracker = self.connection_tracker.borrow();
-        let connections = tracker.active_connections();
-        let _res = TransmitterHelper::send_with_iter(&self.socket, connections, flags, &msg_bytes);
+        self.broadcast_with_flags(flags, msg_bytes.as_slice())
+    }
+    /// Sends an application level ping. Round-trip time, including processing on the client side,
+    /// is reported to `on_pong` callback once the client echoes it back
+    pub fn ping(&self, client: &Uuid) -> ServerResult<()> {
+        let ping_id = self.next_ping_id.get();
+        self.next_ping_id.set(ping_id.wrapping_add(1));
+        self.send_command(client, OmgppPredefinedCmd::PING.to_string(), ping_id, None)?;
+        self.pending_pings
+            .borrow_mut()
+            .insert((client.clone(), ping_id), Instant::now());
         Ok(())
     }
-    pub fn register_on_connect_requested(
+    /// Sets GNS config value (e.g. `k_ESteamNetworkingConfig_TimeoutConnected`, `k_ESteamNetworkingConfig_SendRateMax`).
+    /// gns-rs doesn't expose the listen socket handle, so the value is set globally
+    /// and is inherited by connections accepted afterwards; already established connections keep their values
+    pub fn set_connection_config(
         &self,
-        callback: impl Fn(&Server, &Uuid, &Endpoint) -> bool + 'static,
-    ) {
-        self.callbacks.borrow_mut().on_connect_requested_callback = Box::from(callback);
+        key: ESteamNetworkingConfigValue,
+        value: GnsConfig,
+    ) -> ServerResult<()> {
+        Server::apply_connection_config(key, value)
     }
-    pub fn register_on_connection_state_changed(
-        &self,
-        callback: impl Fn(&Server, &Uuid, &Endpoint, ConnectionState) + 'static,
-    ) {
-        self.callbacks.borrow_mut().on_connection_changed_callback = Some(Box::from(callback));
+    fn apply_connection_config(key: ESteamNetworkingConfigValue, value: GnsConfig) -> ServerResult<()> {
+        let gns = gns().map_err(ServerError::GnsInit)?;
+        gns.utils
+            .set_global_config_value(key, value)
+            .or(Err(ServerError::ConnectionConfig))
     }
-    pub fn register_on_message(
-        &self,
-        callback: impl Fn(&Server, &Uuid, &Endpoint, i64, Vec<u8>) + 'static,
-    ) {
-        self.callbacks.borrow_mut().on_message_callback = Some(Box::from(callback));
+    /// When enabled, sent messages are queued and sent within a single `send_messages` batch
+    /// by `flush` or at the end of `process`, so GNS can pack small messages together.
+    /// Send methods don't report GNS failures in this mode, `flush` does. Disabling flushes queued messages
+    pub fn set_batching(&self, enabled: bool) -> Vec<(Uuid, EResult)> {
+        self.settings.borrow_mut().batching = enabled;
+        match enabled {
+            true => Vec::new(),
+            false => self.flush(),
+        }
     }
-    pub fn register_on_rpc(
-        &mut self,
-        callback: impl Fn(&Server, &Uuid, &Endpoint, bool, i64, u64, i64, Vec<u8>) + 'static,
-    ) {
+    /// Sends messages queued while batching is enabled.
+    /// Returns clients for which a message could not be sent
+    pub fn flush(&self) -> Vec<(Uuid, EResult)> {
+        let mut batch = std::mem::take(&mut *self.pending_batch.borrow_mut());
+        // stable, so messages of the same priority keep their order
+        batch.sort_by_key(|(_client, _connection, _flags, _lane, priority, _data)| Reverse(*priority));
+        let messages = batch
+            .iter()
+            .map(|(_client, connection, flags, lane, _priority, data)| {
+                (connection.clone(), *flags, *lane, data.as_slice())
+            });
+        let send_results = TransmitterHelper::send_batch(self.socket(), messages);
+        let mut failed = Vec::new();
+        for ((client, _connection, _flags, _lane, _priority, data), result) in batch.into_iter().zip(send_results) {
+            match result.right() {
+                Some(err) => failed.push((client, err)),
+                None => self.record_sent(client, data.len()),
+            }
+        }
+        failed
+    }
+    /// Makes GNS send the reliable data queued for the client right away instead of waiting for Nagle,
+    /// e.g. at the end of a round. Messages batched by the server are not submitted, call `flush` first.
+    /// Sends `ControlMessage::FLUSH` to the client, which drops it without invoking callbacks
+    pub fn flush_player(&self, client: &Uuid) -> ServerResult<()> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        match self.flush_connection(connection) {
+            EResult::k_EResultOK => Ok(()),
+            result => Err(ServerError::SendFailed(result)),
+        }
+    }
+    /// Same as `flush_player` for every connected client.
+    /// Returns clients whose queued data could not be sent
+    pub fn flush_all(&self) -> Vec<(Uuid, EResult)> {
+        self.connection_tracker
+            .borrow()
+            .active_client_connections()
+            .filter_map(|(client, connection)| match self.flush_connection(connection) {
+                EResult::k_EResultOK => None,
+                result => Some((client, result)),
+            })
+            .collect()
+    }
+    // gns-rs doesn't wrap `FlushMessagesOnConnection`, a `NoNagle` message makes GNS send the data queued before it
+    fn flush_connection(&self, connection: GnsConnection) -> EResult {
+        let Ok(payload) = create_flush_message() else {
+            return EResult::k_EResultFail;
+        };
+        let results =
+            TransmitterHelper::send(self.socket(), &[connection], k_nSteamNetworkingSend_UnreliableNoNagle, &payload);
+        match results.into_iter().next().and_then(|result| result.right()) {
+            Some(err) => err,
+            None => EResult::k_EResultOK,
+        }
+    }
+    /// Messages and bytes sent to and received from the client since it connected
+    pub fn traffic_stats(&self, client: &Uuid) -> ServerResult<TrafficStats> {
+        if self.connection_tracker.borrow().client_connection(client).is_none() {
+            return Err(ServerError::UnknownPlayer);
+        }
+        Ok(self
+            .traffic_stats
+            .borrow()
+            .get(client)
+            .cloned()
+            .unwrap_or_default())
+    }
+    /// Time since the client sent its last message (or connected, if it has sent nothing yet).
+    /// Unlike GNS timeouts it grows even if the link is alive, so it can be used to detect AFK players
+    pub fn idle_duration(&self, client: &Uuid) -> Option<Duration> {
+        self.last_received_at
+            .borrow()
+            .get(client)
+            .map(|received_at| received_at.elapsed())
+    }
+    /// Messages and bytes of each regular message type sent and received since the server started
+    /// (or `reset_message_type_stats`). Only messages of verified clients are counted
+    pub fn message_type_stats(&self) -> HashMap<i64, TypeStats> {
+        self.type_stats.borrow().clone()
+    }
+    pub fn reset_message_type_stats(&self) {
+        self.type_stats.borrow_mut().clear();
+    }
+    // `is_complete` is false for fragments except the last one, so the message is counted once
+    fn record_type_received(&self, msg_type: i64, bytes: usize, is_complete: bool) {
+        let mut type_stats = self.type_stats.borrow_mut();
+        let stats = type_stats.entry(msg_type).or_default();
+        stats.messages_received += is_complete as u64;
+        stats.bytes_received += bytes as u64;
+    }
+    fn record_type_sent(&self, msg_type: i64, recipients: usize, bytes: usize) {
+        let mut type_stats = self.type_stats.borrow_mut();
+        let stats = type_stats.entry(msg_type).or_default();
+        stats.messages_sent += recipients as u64;
+        stats.bytes_sent += (recipients * bytes) as u64;
+    }
+    fn record_sent(&self, client: Uuid, bytes: usize) {
+        let mut traffic_stats = self.traffic_stats.borrow_mut();
+        let stats = traffic_stats.entry(client).or_default();
+        stats.messages_sent += 1;
+        stats.bytes_sent += bytes as u64;
+    }
+    /// Compresses regular messages above `COMPRESSION_THRESHOLD` bytes.
+    /// Received messages are decompressed regardless of the setting
+    pub fn set_compression(&self, compression: Compression) {
+        self.settings.borrow_mut().compression = compression;
+    }
+    /// Splits reliable messages which do not fit into a single GNS message into fragments
+    /// and reassembles received fragments before `on_message` callback is invoked.
+    /// Must be enabled on both sides. Oversized unreliable messages and messages above `MAX_FRAGMENTED_SIZE`
+    /// still fail with `MessageTooLarge`
+    pub fn set_fragmentation(&self, enabled: bool) {
+        self.settings.borrow_mut().fragmentation = enabled;
+    }
+    /// Limits number of simultaneous connections, including not yet verified ones.
+    /// Connections above the limit are rejected before `on_connect_requested` callback is invoked
+    pub fn set_max_connections(&self, max_connections: Option<usize>) {
+        self.settings.borrow_mut().max_connections = max_connections;
+    }
+    /// Limits number of simultaneous connections from a single IP address (any port), including not yet verified ones.
+    /// Connections above the limit are rejected before `on_connect_requested` callback is invoked
+    pub fn set_max_connections_per_ip(&self, max_connections: Option<usize>) {
+        self.settings.borrow_mut().max_connections_per_ip = max_connections;
+    }
+    /// Limits messages received from each client (including commands, rpc calls and fragments),
+    /// `None` disables the limit. Enforced in `process` before messages are dispatched
+    pub fn set_rate_limit(&self, rate_limit: Option<RateLimit>) {
+        self.settings.borrow_mut().rate_limit = rate_limit;
+    }
+    /// Encrypts data of regular messages with the pre-shared key, clients must use the same key
+    /// (see `Client::set_encryption_key`). Messages which fail to decrypt are dropped. `None` disables it
+    #[cfg(feature = "encryption")]
+    pub fn set_encryption_key(&self, key: Option<&[u8; KEY_SIZE]>) {
+        *self.encryption.borrow_mut() = key.map(Encryption::new);
+    }
+    /// Whether `process` handles connection events or received messages first.
+    /// Disconnects are reported after the client's last messages in both cases.
+    /// With `MessagesFirst` a new client may send auth before its connection event is polled,
+    /// such client is reported as `ConnectedUnverified` on its first command
+    pub fn set_poll_order(&self, poll_order: PollOrder) {
+        self.settings.borrow_mut().poll_order = poll_order;
+    }
+    /// Broadcasts allocate and send messages in chunks of `chunk_size` connections instead of all at once,
+    /// which bounds peak memory with many clients at the cost of more `send_messages` calls. `None` sends at once.
+    /// Connected clients are iterated lazily, so a chunk is the only per-client buffer (see `benches/broadcast.rs`)
+    pub fn set_send_chunk_size(&self, chunk_size: Option<usize>) {
+        self.settings.borrow_mut().send_chunk_size = chunk_size;
+    }
+    /// When false, incoming connections are closed right away without invoking `on_connect_requested`
+    /// (e.g. during a map change). Connected clients are not affected
+    pub fn set_accepting(&self, accepting: bool) {
+        self.settings.borrow_mut().refuse_connections = !accepting;
+    }
+    pub fn is_accepting(&self) -> bool {
+        !self.settings.borrow().refuse_connections
+    }
+    /// Drops duplicates of unreliable messages among the last `window_size` ones received from each client,
+    /// so `on_message` doesn't fire twice for the same message. `0` disables it
+    pub fn set_dedup(&self, window_size: usize) {
+        self.settings.borrow_mut().dedup_window = match window_size {
+            0 => None,
+            window_size => Some(window_size),
+        };
+        self.dedup_windows.borrow_mut().clear();
+    }
+    /// Limits bytes waiting to be sent to the client, `None` client sets the limit of all clients
+    /// without their own one. `None` limit removes it. Enforced for every target of regular messages
+    /// (single client sends, `send_to_many`, broadcasts). A message over the limit is not sent to the client:
+    /// unreliable sends report `SendOutcome::Dropped`, reliable ones fail with `SendFailed(k_EResultLimitExceeded)`
+    /// and multi-client sends return the client with `k_EResultLimitExceeded`
+    pub fn set_outgoing_limit(
+        &self,
+        client: Option<&Uuid>,
+        limit: Option<OutgoingLimit>,
+    ) -> ServerResult<()> {
+        let Some(client) = client else {
+            self.settings.borrow_mut().outgoing_limit = limit;
+            return Ok(());
+        };
+        if self.connection_tracker.borrow().client_connection(client).is_none() {
+            return Err(ServerError::UnknownPlayer);
+        }
+        let mut outgoing_limits = self.outgoing_limits.borrow_mut();
+        match limit {
+            Some(limit) => outgoing_limits.insert(client.clone(), limit),
+            None => outgoing_limits.remove(client),
+        };
+        Ok(())
+    }
+    /// Enables `on_connection_quality_changed` callback, `None` disables it
+    pub fn set_quality_thresholds(&self, thresholds: Option<QualityThresholds>) {
+        self.settings.borrow_mut().quality_thresholds = thresholds;
+        if thresholds.is_none() {
+            self.poor_connections.borrow_mut().clear();
+        }
+    }
+    /// Rejects all new connections from the address. Already connected clients are not affected
+    pub fn ban_ip(&self, ip: IpAddr) {
+        self.settings
+            .borrow_mut()
+            .banned_ips
+            .insert(Server::to_ipv6_mapped(ip));
+    }
+    pub fn unban_ip(&self, ip: IpAddr) -> bool {
+        self.settings
+            .borrow_mut()
+            .banned_ips
+            .remove(&Server::to_ipv6_mapped(ip))
+    }
+    /// Attaches arbitrary data to the client, replacing previously attached one.
+    /// The data is dropped when the client disconnects
+    pub fn set_player_data<T: Any>(&self, client: &Uuid, data: T) -> ServerResult<()> {
+        if self.connection_tracker.borrow().client_connection(client).is_none() {
+            return Err(ServerError::UnknownPlayer);
+        }
+        self.player_data
+            .borrow_mut()
+            .insert(client.clone(), Box::new(data));
+        Ok(())
+    }
+    /// Returns a copy of the attached data if it has type `T`
+    pub fn player_data<T: Any + Clone>(&self, client: &Uuid) -> Option<T> {
+        self.player_data
+            .borrow()
+            .get(client)
+            .and_then(|data| data.downcast_ref::<T>())
+            .cloned()
+    }
+    pub fn with_player_data_mut<T: Any, R>(
+        &self,
+        client: &Uuid,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        self.player_data
+            .borrow_mut()
+            .get_mut(client)
+            .and_then(|data| data.downcast_mut::<T>())
+            .map(f)
+    }
+    pub fn remove_player_data(&self, client: &Uuid) -> bool {
+        self.player_data.borrow_mut().remove(client).is_some()
+    }
+    /// Changes how uuids are assigned to new connections. Already connected clients keep their uuids
+    pub fn set_uuid_scheme(&self, scheme: UuidScheme) {
+        self.connection_tracker.borrow_mut().set_uuid_scheme(scheme);
+    }
+    /// Derives uuids of new connections from connection info (e.g. an identity set by the client) instead of `UuidScheme`.
+    /// A connection whose uuid is already in use is rejected
+    pub fn set_uuid_provider(&self, provider: impl Fn(&GnsConnectionInfo) -> Uuid + 'static) {
+        self.callbacks.borrow_mut().uuid_provider = Some(Box::new(provider));
+    }
+    /// Falls back to the `UuidScheme`
+    pub fn clear_uuid_provider(&self) {
+        self.callbacks.borrow_mut().uuid_provider = None;
+    }
+    /// Forcibly disconnects the client. Connection state callback is fired with `Disconnected` state
+    pub fn kick(&self, client: &Uuid, reason: &str) -> ServerResult<()> {
+        self.close_client(client, END_REASON_APP_GENERIC, reason, false)
+    }
+    /// Sends a final reliable message (e.g. why the client was kicked) and closes the connection with linger,
+    /// so the message is delivered before the client is disconnected. Batched messages are flushed first
+    pub fn send_and_close(
+        &self,
+        client: &Uuid,
+        msg_type: i64,
+        data: &[u8],
+        reason: &str,
+    ) -> ServerResult<()> {
+        self.send_reliable(client, msg_type, data)?;
+        if self.settings.borrow().batching {
+            _ = self.flush();
+        }
+        self.close_client(client, END_REASON_APP_GENERIC, reason, true)
+    }
+    /// Closes every connection, including not yet verified ones.
+    /// Connections linger so already queued reliable messages are delivered before the close
+    pub fn shutdown(&self, reason: &str) {
+        let clients = self.connection_tracker.borrow().tracked_clients();
+        for client in clients.iter() {
+            _ = self.close_client(client, END_REASON_APP_GENERIC, reason, true);
+        }
+    }
+    /// Accepts or rejects a client for which `on_connect_requested` returned `ConnectDecision::Pending`.
+    /// Fails with `UnknownPlayer` if the client is not pending (e.g. it has already given up)
+    pub fn resolve_pending<D: Into<ConnectDecision>>(&self, client: &Uuid, decision: D) -> ServerResult<()> {
+        if !self.pending_connects.borrow_mut().remove(client) {
+            return Err(ServerError::UnknownPlayer);
+        }
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        match decision.into() {
+            ConnectDecision::Pending => {
+                self.pending_connects.borrow_mut().insert(client.clone());
+                Ok(())
+            }
+            decision => self.apply_connect_decision(self.socket(), client, connection, decision),
+        }
+    }
+    /// Clients waiting for `resolve_pending`
+    pub fn pending_clients(&self) -> Vec<Uuid> {
+        self.pending_connects.borrow().iter().cloned().collect()
+    }
+    /// Kicks every tracked client (including not yet verified ones) matching the predicate.
+    /// Returns kicked clients
+    pub fn disconnect_where(&self, pred: impl Fn(&Uuid) -> bool, reason: &str) -> Vec<Uuid> {
+        let clients = self.connection_tracker.borrow().tracked_clients();
+        clients
+            .into_iter()
+            .filter(|client| pred(client))
+            .filter(|client| self.kick(client, reason).is_ok())
+            .collect()
+    }
+    /// Callback decides whether to accept the client. Returning `bool` is the same as
+    /// `ConnectDecision::Accept`/`ConnectDecision::Reject(RejectReason::default())`
+    pub fn register_on_connect_requested<D: Into<ConnectDecision>>(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint) -> D + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_connect_requested_callback =
+            Box::new(move |server, client, endpoint, _info| callback(server, client, endpoint).into());
+    }
+    /// Same as `register_on_connect_requested`, but the callback receives the whole GNS connection info
+    /// (remote address and port, connection description etc.) e.g. for address based decisions
+    pub fn register_on_connect_requested_with_info<D: Into<ConnectDecision>>(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &GnsConnectionInfo) -> D + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_connect_requested_callback =
+            Box::new(move |server, client, _endpoint, info| callback(server, client, info).into());
+    }
+    /// Decides whether a connected client may leave the unverified state, `args` are sent by the client
+    /// as its first command (see `Client::register_on_auth`). Until then the client receives no broadcasts
+    /// and is closed after the auth timeout. Without the callback every client is accepted
+    pub fn register_on_authenticate(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, &[String]) -> bool + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_authenticate_callback = Some(Box::new(callback));
+    }
+    /// Time without any reply after which an established connection is closed
+    /// and reported as `ConnectionState::TimedOut`. Applies to connections accepted afterwards
+    pub fn set_connection_timeout(&self, timeout: Duration) -> ServerResult<()> {
+        Server::apply_connection_config(
+            ESteamNetworkingConfigValue::k_ESteamNetworkingConfig_TimeoutConnected,
+            GnsConfig::Int32(timeout.as_millis() as _),
+        )?;
+        self.settings.borrow_mut().connection_timeout = Some(timeout);
+        Ok(())
+    }
+    /// Timeout set by `set_connection_timeout` or `ServerBuilder::connection_timeout`, GNS default (10s) otherwise.
+    /// Values set with `set_connection_config` are not reflected
+    pub fn connection_timeout(&self) -> Duration {
+        self.settings
+            .borrow()
+            .connection_timeout
+            .unwrap_or(DEFAULT_CONNECTION_TIMEOUT)
+    }
+    /// Time a connected client has to pass authentication before it is closed
+    pub fn set_auth_timeout(&self, timeout: Duration) {
+        self.connection_tracker
+            .borrow_mut()
+            .set_unverified_connection_expire_period(timeout);
+    }
+    /// Time a disconnected client has to reconnect and resume its session (keeping its uuid),
+    /// see `ConnectionState::Reconnected`
+    pub fn set_session_expire_period(&self, period: Duration) {
+        self.sessions.borrow_mut().set_expire_period(period);
+    }
+    pub fn session_expire_period(&self) -> Duration {
+        self.sessions.borrow().expire_period()
+    }
+    /// Forgets the client's session, so the client can't resume it (e.g. after a kick or ban)
+    pub fn end_session(&self, client: &Uuid) {
+        self.sessions.borrow_mut().remove(client);
+    }
+    pub fn register_on_connection_state_changed(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, ConnectionState, Option<&DisconnectReason>) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_connection_changed_callback = Some(Box::from(callback));
+    }
+    /// Callback receives whether the message was sent reliably and the message type the client passed to `Client::send`
+    pub fn register_on_message(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, bool, i64, Vec<u8>) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_message_callback = Some(Box::from(callback));
+    }
+    /// Same as `register_on_message` but the callback runs on a worker of the callback pool
+    /// (see `set_callback_workers`), so slow message handling doesn't stall `process`.
+    /// Messages of a client are handled one at a time in the order they were received.
+    /// `Server` can't leave its thread, use `ServerTasks::execute` to reply.
+    /// Takes precedence over `on_message`, typed message callbacks still run on the polling thread
+    pub fn register_on_message_threaded(
+        &self,
+        callback: impl Fn(&ServerTasks, &Uuid, &Endpoint, bool, i64, Vec<u8>) + Send + Sync + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_threaded_message_callback = Some(Arc::new(callback));
+    }
+    pub fn unregister_on_message_threaded(&self) {
+        self.callbacks.borrow_mut().on_threaded_message_callback = None;
+    }
+    /// Number of worker threads running callbacks registered with `register_on_message_threaded`.
+    /// `0` stops the workers (after they finish the messages already dispatched to them)
+    /// and runs the callbacks within `process` again
+    pub fn set_callback_workers(&self, workers: usize) {
+        // the old pool is dropped outside of the borrow, it waits for its workers
+        let _old_pool = self.callback_pool.replace(match workers {
+            0 => None,
+            workers => Some(CallbackPool::new(workers)),
+        });
+    }
+    pub fn callback_workers(&self) -> usize {
+        self.callback_pool
+            .borrow()
+            .as_ref()
+            .map_or(0, |pool| pool.worker_count())
+    }
+    /// Callback is invoked for messages received from a connection which is not tracked anymore
+    /// (e.g. already half-closed). Such messages are dropped and don't fail `process`
+    pub fn register_on_unrouted_message(
+        &self,
+        callback: impl Fn(&Server, &GnsConnection, Vec<u8>) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_unrouted_message_callback = Some(Box::from(callback));
+    }
+    /// Callback receives decoded messages of type `M::MSG_TYPE` instead of `on_message`.
+    /// Messages which cannot be decoded are dropped
+    pub fn register_on_typed_message<M: TypedMessage + 'static>(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, M) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_typed_message_callbacks.insert(
+            M::MSG_TYPE,
+            Box::new(move |server, client, endpoint, data| {
+                if let Ok(message) = M::decode(data) {
+                    callback(server, client, endpoint, message)
+                }
+            }),
+        );
+    }
+    pub fn unregister_on_typed_message<M: TypedMessage>(&self) {
+        self.callbacks
+            .borrow_mut()
+            .on_typed_message_callbacks
+            .remove(&M::MSG_TYPE);
+    }
+    /// Calls of `method_id` are dispatched to `handler` instead of `on_rpc`. Returned args are sent back
+    /// as the reply with the call's `request_id` (see `Client::call_rpc_with_reply`), `None` sends no reply.
+    /// Like other callbacks, handlers are not invoked by `poll_into`
+    pub fn register_rpc_method(
+        &self,
+        method_id: i64,
+        handler: impl Fn(&Server, &Uuid, &Endpoint, RpcArgs) -> Option<RpcArgs> + 'static,
+    ) -> ServerResult<()> {
+        self.rpc_registry
+            .borrow_mut()
+            .register(method_id, Box::new(handler))
+            .or(Err(ServerError::RpcMethodRegistered(method_id)))
+    }
+    /// Returns false if the method was not registered
+    pub fn unregister_rpc_method(&self, method_id: i64) -> bool {
+        self.rpc_registry.borrow_mut().unregister(method_id)
+    }
+    pub fn register_on_pong(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, Duration) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_pong_callback = Some(Box::from(callback));
+    }
+    pub fn register_on_rpc(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, bool, i64, u64, i64, Vec<u8>) + 'static,
+    ) {
         self.callbacks.borrow_mut().on_rpc_callback = Some(Box::from(callback));
     }
+    /// Invoked with `true` when the client's connection becomes poor according to `QualityThresholds`
+    /// and with `false` when it recovers
+    pub fn register_on_connection_quality_changed(
+        &self,
+        callback: impl Fn(&Server, &Uuid, &Endpoint, bool, &ConnectionRealTimeStatus) + 'static,
+    ) {
+        self.callbacks.borrow_mut().on_connection_quality_changed_callback = Some(Box::new(callback));
+    }
+    pub fn unregister_on_connection_quality_changed(&self) {
+        self.callbacks.borrow_mut().on_connection_quality_changed_callback = None;
+    }
+    pub fn unregister_on_authenticate(&self) {
+        self.callbacks.borrow_mut().on_authenticate_callback = None;
+    }
+    /// Restores the default behaviour: every client is accepted
+    pub fn unregister_on_connect_requested(&self) {
+        self.callbacks.borrow_mut().on_connect_requested_callback =
+            Box::new(|_server, _id, _endpoint, _info| ConnectDecision::Accept);
+    }
+    pub fn unregister_on_connection_state_changed(&self) {
+        self.callbacks.borrow_mut().on_connection_changed_callback = None;
+    }
+    /// Filters regular messages received from verified clients, a filter returning false drops the message
+    /// before `on_message` and typed callbacks. Filters are called in the order they were added
+    pub fn add_inbound_filter(&self, filter: impl Fn(&Uuid, i64, &[u8]) -> bool + 'static) {
+        self.callbacks.borrow_mut().inbound_filters.push(Box::new(filter));
+    }
+    pub fn clear_inbound_filters(&self) {
+        self.callbacks.borrow_mut().inbound_filters.clear();
+    }
+    /// Rewrites data of sent regular messages (e.g. encrypts it) before it is compressed and encoded.
+    /// Transforms are chained in the order they were added. Commands and rpc calls are not transformed
+    pub fn add_outbound_transform(&self, transform: impl Fn(&[u8]) -> Vec<u8> + 'static) {
+        self.callbacks.borrow_mut().outbound_transforms.push(Box::new(transform));
+    }
+    pub fn clear_outbound_transforms(&self) {
+        self.callbacks.borrow_mut().outbound_transforms.clear();
+    }
+    pub fn unregister_on_message(&self) {
+        self.callbacks.borrow_mut().on_message_callback = None;
+    }
+    pub fn unregister_on_unrouted_message(&self) {
+        self.callbacks.borrow_mut().on_unrouted_message_callback = None;
+    }
+    pub fn unregister_on_pong(&self) {
+        self.callbacks.borrow_mut().on_pong_callback = None;
+    }
+    /// Invoked once per `process` (`poll_into`, `drain_into`, `process_blocking`) call after events and messages
+    /// are handled, however many passes the call makes, e.g. to step the game
+    /// and broadcast its state. Messages batched by the callback are sent within the same call.
+    /// The callback may register another one, unregistering itself from within has no effect
+    pub fn register_on_tick(&self, callback: impl FnMut(&Server) + 'static) {
+        self.callbacks.borrow_mut().on_tick_callback = Some(Box::new(callback));
+    }
+    pub fn unregister_on_tick(&self) {
+        self.callbacks.borrow_mut().on_tick_callback = None;
+    }
+    pub fn unregister_on_rpc(&self) {
+        self.callbacks.borrow_mut().on_rpc_callback = None;
+    }
+    /// Runs the connection state machine of `process` on a synthetic event of a tracked client, so accept
+    /// and disconnect callbacks can be tested without waiting for GNS, e.g. for a timeout.
+    /// Connect requests need a real client, see `Loopback`. Fails with `UnknownPlayer` if the client is not tracked.
+    /// The client's GNS connection is closed after a synthetic disconnect
+    #[cfg(feature = "test-util")]
+    pub fn inject_connection_event(&self, event: &synthetic_event::SyntheticConnectionEvent) -> ServerResult<()> {
+        let (connection, endpoint, listener) = {
+            let connection_tracker = self.connection_tracker.borrow();
+            let connection = connection_tracker
+                .client_connection(&event.client)
+                .ok_or(ServerError::UnknownPlayer)?;
+            let endpoint = connection_tracker
+                .client_endpoint(&event.client)
+                .cloned()
+                .ok_or(ServerError::UnknownPlayer)?;
+            let listener = connection_tracker.client_listener(&event.client).unwrap_or(0);
+            (connection, endpoint, listener)
+        };
+        let socket = &self.sockets[listener];
+        let transition = ConnectionTransition::synthetic(
+            connection.clone(),
+            endpoint,
+            event.old_state,
+            event.new_state,
+            event.end_reason,
+            event.end_debug.clone(),
+        );
+        let is_disconnect = transition.is_disconnect();
+        let result = self.process_connection_events(
+            transition,
+            listener,
+            socket,
+            &self.callbacks.borrow(),
+            &self.connection_tracker,
+        );
+        if is_disconnect {
+            socket.close_connection(connection, event.end_reason as _, &event.end_debug, false);
+        }
+        result
+    }
+    // disconnect events are not handled but collected, so they can be handled after the messages
+    fn poll_listener_events<const N: usize>(
+        &self,
+        listener: usize,
+        socket: &GnsSocket<'static, 'static, IsServer>,
+        stats: &mut ProcessStats,
+        errors: &mut Vec<ServerError>,
+        disconnects: &mut Vec<ConnectionTransition>,
+    ) {
+        socket.poll_event::<N>(|event| {
+            stats.events += 1;
+            let transition = ConnectionTransition::from_event(&event);
+            if transition.is_disconnect() {
+                disconnects.push(transition);
+                return;
+            }
+            if let Err(err) = Server::process_connection_events(
+                self,
+                transition,
+                listener,
+                socket,
+                &self.callbacks.borrow(),
+                &self.connection_tracker,
+            ) {
+                errors.push(err);
+            }
+        });
+    }
+    fn poll_listener_messages<const N: usize>(
+        &self,
+        socket: &GnsSocket<'static, 'static, IsServer>,
+        stats: &mut ProcessStats,
+        errors: &mut Vec<ServerError>,
+    ) -> usize {
+        socket.poll_messages::<N>(|msg| {
+            stats.messages += 1;
+            if let Err(err) = Server::process_messages(
+                self,
+                msg,
+                &self.connection_tracker,
+                &self.callbacks.borrow(),
+            ) {
+                errors.push(err);
+            }
+        })
+    }
     fn process_connection_events(
         &self,
-        event: GnsConnectionEvent,
+        transition: ConnectionTransition,
+        listener: usize,
         socket: &GnsSocket<IsServer>,
         callbacks: &ServerCallbacks,
         connection_tracker: &RefCell<ConnectionTracker>,
     ) -> ServerResult<()> {
-        let endpoint = event.info().to_endpoint();
-        let client_uuid = ConnectionTracker::generate_endpoint_uuid(&endpoint);
-        match (event.old_state(), event.info().state()) {
+        let endpoint = transition.endpoint().clone();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("connection_event", endpoint = ?endpoint, listener).entered();
+        let tracked_uuid = connection_tracker
+            .borrow()
+            .client_by_connection(&transition.connection())
+            .cloned();
+        match (transition.old_state(), transition.new_state()) {
             // client tries to connect
             (
                 ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_None,
                 ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connecting,
             ) => {
-                if let Some(cb) = &callbacks.on_connection_changed_callback{
-                    cb(self,&client_uuid, &endpoint, ConnectionState::Connecting);      // TODO add host and port as parameters
+                // connect requests come from GNS only
+                let Some(info) = transition.info() else {
+                    return Err(ServerError::UnknownConnection);
+                };
+                if self.settings.borrow().refuse_connections {
+                    #[cfg(feature = "tracing")]
+                    tracing::info!("connect request rejected: not accepting connections");
+                    socket.close_connection(
+                        transition.connection(),
+                        END_REASON_APP_GENERIC as _,
+                        "Server is not accepting connections",
+                        false,
+                    );
+                    return Ok(());
                 }
-                let should_accept = (callbacks.on_connect_requested_callback)(self,&client_uuid,&endpoint);
-                if should_accept {
-                    socket.accept(event.connection()).or_else(|_err| {
-                        ServerResult::Err("Cannot accept the connection".to_string())
-                    })?;
-                } else {
-                    // watch all possible reasons in ESteamNetConnectionEnd at steamworks_sdk_160\sdk\public\steam\steamnetworkingtypes.h (SteamworksSDK)
+                let is_banned = self.settings.borrow().banned_ips.contains(&Server::to_ipv6_mapped(endpoint.ip));
+                if is_banned {
+                    #[cfg(feature = "tracing")]
+                    tracing::info!("connect request rejected: banned ip");
                     socket.close_connection(
-                        event.connection(),
-                        0,      // k_ESteamNetConnectionEnd_Invalid 
-                        "You are not allowed to connect",
+                        transition.connection(),
+                        END_REASON_APP_GENERIC as _,
+                        "You are banned",
                         false,
                     );
+                    return Ok(());
+                }
+                let max_connections = self.settings.borrow().max_connections;
+                if let Some(max_connections) = max_connections {
+                    if connection_tracker.borrow().connection_count() >= max_connections {
+                        #[cfg(feature = "tracing")]
+                        tracing::info!(max_connections, "connect request rejected: server is full");
+                        socket.close_connection(
+                            transition.connection(),
+                            END_REASON_APP_GENERIC as _,
+                            "Server is full",
+                            false,
+                        );
+                        return Ok(());
+                    }
+                }
+                let max_connections_per_ip = self.settings.borrow().max_connections_per_ip;
+                if let Some(max_connections_per_ip) = max_connections_per_ip {
+                    if connection_tracker.borrow().connection_count_from(&endpoint.ip) >= max_connections_per_ip {
+                        #[cfg(feature = "tracing")]
+                        tracing::info!(max_connections_per_ip, "connect request rejected: too many connections from the address");
+                        socket.close_connection(
+                            transition.connection(),
+                            END_REASON_APP_GENERIC as _,
+                            "Too many connections from your address",
+                            false,
+                        );
+                        return Ok(());
+                    }
                 }
+                let client_uuid = match &callbacks.uuid_provider {
+                    Some(provider) => provider(info),
+                    None => connection_tracker.borrow().generate_client_uuid(&endpoint),
+                };
+                // e.g. the same address with `UuidScheme::AddressBased`; the tracked client must not be overwritten
+                if connection_tracker.borrow().client_connection(&client_uuid).is_some() {
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(client = %client_uuid, "connect request rejected: uuid is already in use");
+                    socket.close_connection(
+                        transition.connection(),
+                        END_REASON_APP_GENERIC as _,
+                        "Already connected",
+                        false,
+                    );
+                    return Ok(());
+                }
+                connection_tracker.borrow_mut().track_client_connecting(
+                    client_uuid.clone(),
+                    endpoint,
+                    transition.connection(),
+                    listener,
+                );
+                self.last_received_at
+                    .borrow_mut()
+                    .insert(client_uuid.clone(), Instant::now());
+                #[cfg(feature = "tracing")]
+                tracing::info!(client = %client_uuid, "connect requested");
+                self.notify_connection_changed(&client_uuid, &endpoint, ConnectionState::Connecting, None);
+                let decision =
+                    (callbacks.on_connect_requested_callback)(self, &client_uuid, &endpoint, info);
+                self.apply_connect_decision(socket, &client_uuid, transition.connection(), decision)?;
             }
             // client disconnected gracefully (? or may be not)
             (
                 ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connecting | ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
                  ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ClosedByPeer | ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_None |ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ProblemDetectedLocally,
             ) => {
-                connection_tracker.borrow_mut().track_client_disconnected(&client_uuid);
-                let state = connection_tracker.borrow().state(&client_uuid);
-                if let Some(cb) = &callbacks.on_connection_changed_callback {
-                    cb(self,&client_uuid, &endpoint, state);
-                }
+                let Some(client_uuid) = tracked_uuid else {
+                    return Ok(());
+                };
+                self.track_client_disconnected(&client_uuid);
+                let reason = DisconnectReason {
+                    end_reason: transition.end_reason(),
+                    debug: transition.end_debug().to_string(),
+                };
+                let state = match reason.end_reason {
+                    END_REASON_MISC_TIMEOUT => ConnectionState::TimedOut,
+                    _ => ConnectionState::Disconnected,
+                };
+                #[cfg(feature = "tracing")]
+                tracing::info!(
+                    client = %client_uuid,
+                    end_reason = reason.end_reason,
+                    debug = %reason.debug,
+                    "client disconnected"
+                );
+                self.notify_connection_changed(&client_uuid, &endpoint, state, Some(&reason));
             }
             // client connected but auth required
             (
                 ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connecting,
                 ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
             ) => {
-                connection_tracker.borrow_mut().track_client_connected_unverified(client_uuid.clone(),endpoint, event.connection());
-                let state = connection_tracker.borrow().state(&client_uuid);
-                if let Some(cb) = &callbacks.on_connection_changed_callback {
-                    cb(self,&client_uuid, &endpoint, state);
+                let Some(client_uuid) = tracked_uuid else {
+                    return Err(ServerError::UnknownConnection);
+                };
+                // already connected by its first message, see `track_connected_by_message`
+                if connection_tracker.borrow().state(&client_uuid) != ConnectionState::Connecting {
+                    return Ok(());
                 }
+                connection_tracker.borrow_mut().track_client_connected_unverified(client_uuid.clone(),endpoint, transition.connection());
+                #[cfg(feature = "tracing")]
+                tracing::info!(client = %client_uuid, "client connected, waiting for auth");
+                let state = connection_tracker.borrow().state(&client_uuid);
+                self.notify_connection_changed(&client_uuid, &endpoint, state, None);
             }
 
             (_, _) => (),
@@ -361,48 +1929,96 @@ impl<'a> Server<'a> {
     ) -> ServerResult<()> {
         let data = event.payload();
         let connection = event.connection();
+        let reliable = event.flags() & k_nSteamNetworkingSend_Reliable != 0;
         let sender = connection_tracker
             .borrow()
             .client_by_connection(&connection)
-            .cloned()
-            .ok_or_else(|| "Unknown connection".to_string())?;
+            .cloned();
+        let Some(sender) = sender else {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(connection = ?connection, size = data.len(), "message from unknown connection");
+            if let Some(cb) = &callbacks.on_unrouted_message_callback {
+                cb(self, &connection, Vec::from(data));
+            }
+            return Ok(());
+        };
+        {
+            let mut traffic_stats = self.traffic_stats.borrow_mut();
+            let stats = traffic_stats.entry(sender.clone()).or_default();
+            stats.messages_received += 1;
+            stats.bytes_received += data.len() as u64;
+        }
+        self.last_received_at
+            .borrow_mut()
+            .insert(sender.clone(), Instant::now());
+        let rate_limit = self.settings.borrow().rate_limit;
+        if let Some(rate_limit) = rate_limit {
+            if !self.rate_limiter.borrow_mut().check(&sender, data.len(), &rate_limit) {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(client = %sender, policy = ?rate_limit.policy, "rate limit exceeded");
+                if rate_limit.policy == RateLimitPolicy::Kick {
+                    _ = self.kick(&sender, "Rate limit exceeded");
+                }
+                return Ok(());
+            }
+        }
         let is_sender_verified =
             connection_tracker.borrow().state(&sender) == ConnectionState::Connected;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!("message", client = %sender, verified = is_sender_verified).entered();
 
         let endpoint = connection_tracker
             .borrow()
             .client_endpoint(&sender)
             .cloned()
-            .ok_or_else(|| "Unknown endpoint".to_string())?;
+            .ok_or(ServerError::UnknownPlayer)?;
 
         if let Some(decoded) = GeneralOmgppMessage::parse_from_bytes(data).ok() {
             // we decoded the message
             match decoded.data {
                 Some(Data::Message(message)) => {
-                    // cb stands for callback
-                    if let Some(cb) = &callbacks.on_message_callback {
-                        if is_sender_verified {
-                            cb(self, &sender, &endpoint, message.type_, message.data)
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(msg_type = message.type_, size = message.data.len(), "message received");
+                    if is_sender_verified && !self.is_duplicate(&sender, reliable, message.sequence) {
+                        self.record_type_received(message.type_, data.len(), true);
+                        let msg_data =
+                            self.open_payload(&sender, message.compression, message.data, MAX_DECOMPRESSED_MESSAGE_SIZE);
+                        if let Some(msg_data) = msg_data {
+                            self.notify_message(&sender, &endpoint, reliable, message.type_, msg_data);
                         }
                     }
                 }
-                Some(Data::Rpc(rpc_call)) => {
-                    if let Some(rpc_callback) = &callbacks.on_rpc_callback {
-                        if is_sender_verified {
-                            rpc_callback(
-                                self,
-                                &sender,
-                                &endpoint,
-                                rpc_call.reliable,
-                                rpc_call.method_id,
-                                rpc_call.request_id,
-                                rpc_call.arg_type,
-                                rpc_call.arg_data,
-                            );
-                        }
+                Some(Data::Fragment(fragment)) => {
+                    if !is_sender_verified || !self.settings.borrow().fragmentation {
+                        return Ok(());
+                    }
+                    let fragment_type = fragment.type_;
+                    let message = self
+                        .fragments
+                        .borrow_mut()
+                        .entry(sender.clone())
+                        .or_default()
+                        .push(fragment);
+                    self.record_type_received(fragment_type, data.len(), message.is_some());
+                    let Some((msg_type, compression, msg_data)) = message else {
+                        return Ok(());
                     };
+                    if let Some(msg_data) = self.open_payload(&sender, compression, msg_data, MAX_DECOMPRESSED_SIZE) {
+                        // fragments are always sent reliably
+                        self.notify_message(&sender, &endpoint, true, msg_type, msg_data);
+                    }
+                }
+                Some(Data::Rpc(rpc_call)) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(method_id = rpc_call.method_id, request_id = rpc_call.request_id, "rpc received");
+                    if is_sender_verified {
+                        self.notify_rpc(&sender, &endpoint, rpc_call);
+                    }
                 }
                 Some(Data::Cmd(cmd)) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(cmd = ?cmd.cmd, "command received");
+                    self.track_connected_by_message(&sender, &endpoint);
                     self.cmd_handlers
                         .borrow()
                         .handle(self, &sender, &endpoint, &cmd);
@@ -411,6 +2027,8 @@ impl<'a> Server<'a> {
             }
         } else {
             // cannot decode message;
+            #[cfg(feature = "tracing")]
+            tracing::debug!(size = data.len(), "cannot decode message");
         }
         Ok(())
     }
@@ -422,79 +2040,708 @@ impl<'a> Server<'a> {
         data: &[u8],
         flags: i32,
     ) -> ServerResult<()> {
+        match self.try_send_with_flags(client, msg_type, data, flags)? {
+            Some(result) => Err(ServerError::SendFailed(result)),
+            None => Ok(()),
+        }
+    }
+    // returns GNS result if the message could not be sent
+    fn try_send_with_flags(
+        &self,
+        client: &Uuid,
+        msg_type: i64,
+        data: &[u8],
+        flags: i32,
+    ) -> ServerResult<Option<EResult>> {
         let connection = self
             .connection_tracker
             .borrow()
             .client_connection(client)
-            .ok_or_else(|| "There is not such client to send")?;
+            .ok_or(ServerError::UnknownPlayer)?;
+        let failed =
+            self.send_regular_message(Targets::Clients(vec![(client.clone(), connection)]), flags, msg_type, data)?;
+        Ok(failed.into_iter().next().map(|(_client, result)| result))
+    }
+    fn send_to_many_with_flags(
+        &self,
+        clients: &[Uuid],
+        msg_type: i64,
+        data: &[u8],
+        flags: i32,
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let targets = {
+            let tracker = self.connection_tracker.borrow();
+            clients
+                .iter()
+                .map(|client| {
+                    tracker
+                        .client_connection(client)
+                        .map(|connection| (client.clone(), connection))
+                        .ok_or(ServerError::UnknownPlayer)
+                })
+                .collect::<ServerResult<Vec<_>>>()?
+        };
 
-        let msg_bytes = Server::create_regular_message(msg_type, data)
-            .or_else(|_or| Err("Cannot create general message".to_string()))?;
+        self.send_regular_message(Targets::Clients(targets), flags, msg_type, data)
+    }
+    fn broadcast_with_flags(&self, flags: i32, data: &[u8]) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.broadcast_filtered_with_flags(flags, data, |_client| true)
+    }
+    fn broadcast_filtered_with_flags(
+        &self,
+        flags: i32,
+        data: &[u8],
+        filter: impl Fn(&Uuid) -> bool,
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let connection_tracker = self.connection_tracker.borrow();
+        let targets = connection_tracker
+            .active_client_connections()
+            .filter(|(client, _connection)| filter(client));
+        self.send_to_clients(targets, flags, data)
+    }
+    fn active_targets(&self, filter: impl Fn(&Uuid) -> bool) -> Vec<(Uuid, GnsConnection)> {
+        self.connection_tracker
+            .borrow()
+            .active_client_connections()
+            .filter(|(client, _connection)| filter(client))
+            .collect()
+    }
+    // unlike `active_targets` the predicate is called without the tracker borrowed
+    fn matching_targets(&self, pred: impl Fn(&Uuid) -> bool) -> Vec<(Uuid, GnsConnection)> {
+        let mut targets = self.active_targets(|_client| true);
+        targets.retain(|(client, _connection)| pred(client));
+        targets
+    }
+    /// Encodes and sends the regular message.
+    /// Reliable messages which do not fit into a single GNS message are split into fragments if fragmentation is enabled
+    fn send_regular_message(
+        &self,
+        targets: Targets,
+        flags: i32,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.send_regular_message_on_lane(targets, flags, 0, 0, msg_type, data)
+    }
+    fn send_regular_message_on_lane(
+        &self,
+        targets: Targets,
+        flags: i32,
+        lane: u16,
+        priority: u8,
+        msg_type: i64,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        let (compression, payload, plain_size) = self.encode_payload(msg_type, data)?;
+        let msg_bytes = create_regular_message(msg_type, &payload, compression)
+            .or(Err(ServerError::MessageEncode))?;
+        let is_reliable = flags & k_nSteamNetworkingSend_Reliable != 0;
+        // the client decompresses a single message up to `MAX_DECOMPRESSED_MESSAGE_SIZE`
+        let is_oversized = msg_bytes.len() > MAX_MESSAGE_SIZE || plain_size > MAX_DECOMPRESSED_MESSAGE_SIZE;
+        let is_fragmented = is_oversized && is_reliable && self.settings.borrow().fragmentation;
+        // checked before the outgoing limits, an oversized message must not kick anyone
+        let (size, max) = match is_fragmented {
+            true => (payload.len().max(plain_size), MAX_FRAGMENTED_SIZE),
+            false if msg_bytes.len() > MAX_MESSAGE_SIZE => (msg_bytes.len(), MAX_MESSAGE_SIZE),
+            false => (plain_size, MAX_DECOMPRESSED_MESSAGE_SIZE),
+        };
+        if size > max {
+            return Err(ServerError::MessageTooLarge { size, max });
+        }
+        let (targets, mut failed) = self.apply_outgoing_limits(targets, msg_bytes.len());
+        if !is_fragmented {
+            let (target_count, send_failed) =
+                self.send_to_targets_on_lane(targets, flags, lane, priority, msg_bytes.as_slice())?;
+            self.record_type_sent(msg_type, target_count - send_failed.len(), msg_bytes.len());
+            failed.extend(send_failed);
+            return Ok(failed);
+        }
+        let message_id = self.next_fragmented_message_id.get();
+        self.next_fragmented_message_id.set(message_id.wrapping_add(1));
+        let fragments = create_fragment_messages(message_id, msg_type, compression, &payload)
+            .or(Err(ServerError::MessageEncode))?;
 
-        // TODO check send result
-        let _send_result =
-            TransmitterHelper::send(&self.socket, &[connection], flags, msg_bytes.as_slice());
-        Ok(())
+        // every fragment goes to the same clients, so the targets are collected
+        let mut targets = match targets {
+            Targets::Clients(clients) => clients,
+            Targets::Active(filter) => self.active_targets(filter),
+        };
+        let target_count = targets.len();
+        let fragments_size: usize = fragments.iter().map(|fragment| fragment.len()).sum();
+        let mut send_failed: Vec<(Uuid, EResult)> = Vec::new();
+        for fragment in fragments {
+            let fragment_failed =
+                self.send_to_clients_on_lane(targets.iter().cloned(), flags, lane, priority, fragment.as_slice())?;
+            // the message cannot be reassembled without the fragment, so skip the rest of them
+            targets.retain(|(client, _connection)| {
+                !fragment_failed.iter().any(|(failed_client, _result)| failed_client == client)
+            });
+            send_failed.extend(fragment_failed);
+        }
+        self.record_type_sent(msg_type, target_count - send_failed.len(), fragments_size);
+        failed.extend(send_failed);
+        Ok(failed)
+    }
+    // drops targets which would exceed their outgoing limit (see `set_outgoing_limit`), they are reported
+    // as `k_EResultLimitExceeded`. Without any limit the targets are kept as they are, so broadcasts still
+    // iterate connections lazily. Limited targets are collected first, `OverflowPolicy::Kick` needs the tracker
+    fn apply_outgoing_limits<'f>(&self, targets: Targets<'f>, size: usize) -> (Targets<'f>, Vec<(Uuid, EResult)>) {
+        let is_limited = self.settings.borrow().outgoing_limit.is_some() || !self.outgoing_limits.borrow().is_empty();
+        if !is_limited {
+            return (targets, Vec::new());
+        }
+        let targets = match targets {
+            Targets::Clients(clients) => clients,
+            Targets::Active(filter) => self.active_targets(filter),
+        };
+        let mut over_limit = Vec::new();
+        let targets = targets
+            .into_iter()
+            .filter(|(client, _connection)| {
+                // a client which could not be checked (e.g. kicked meanwhile) can't be sent to either
+                let fits = self.fits_outgoing_limit(client, size).unwrap_or(false);
+                if !fits {
+                    over_limit.push((client.clone(), EResult::k_EResultLimitExceeded));
+                }
+                fits
+            })
+            .collect();
+        (Targets::Clients(targets), over_limit)
+    }
+    // transforms, compresses and encrypts data of the regular message.
+    // Returns the compression, the payload and the size the receiver decompresses it to
+    fn encode_payload(&self, msg_type: i64, data: &[u8]) -> ServerResult<(u32, Vec<u8>, usize)> {
+        if ControlMessage::is_reserved(msg_type) {
+            return Err(ServerError::ReservedMessageType(msg_type));
+        }
+        let transformed = self.transform_outbound(data);
+        let data = transformed.as_deref().unwrap_or(data);
+        let (compression, payload) = self.settings.borrow().compression.compress(data);
+        let payload = self.encrypt_payload(payload)?;
+        Ok((compression, payload, data.len()))
+    }
+    // sends again messages of `send_unreliable_acked` the clients haven't acknowledged in time
+    fn retransmit_unacked(&self) {
+        let timeout = self
+            .settings
+            .borrow()
+            .ack_resend_timeout
+            .unwrap_or(DEFAULT_ACK_RESEND_TIMEOUT);
+        let due = self
+            .ack_senders
+            .borrow_mut()
+            .iter_mut()
+            .map(|(client, sender)| (client.clone(), sender.due_retransmits(timeout, MAX_ACK_RETRANSMITS)))
+            .filter(|(_client, messages)| !messages.is_empty())
+            .collect::<Vec<_>>();
+        for (client, messages) in due {
+            let Some(connection) = self.connection_tracker.borrow().client_connection(&client) else {
+                continue;
+            };
+            for msg_bytes in messages {
+                // failures are recovered by the next retransmit
+                _ = self.send_to_clients(
+                    vec![(client.clone(), connection.clone())],
+                    k_nSteamNetworkingSend_Unreliable,
+                    &msg_bytes,
+                );
+            }
+        }
+    }
+    // payload of regular messages is encrypted after compression, so it is decrypted before decompression
+    fn encrypt_payload(&self, payload: Vec<u8>) -> ServerResult<Vec<u8>> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption) = self.encryption.borrow().as_ref() {
+            return encryption.encrypt(&payload).or(Err(ServerError::MessageEncode));
+        }
+        Ok(payload)
+    }
+    fn decrypt_payload(&self, payload: Vec<u8>) -> Result<Vec<u8>, String> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption) = self.encryption.borrow().as_ref() {
+            return encryption.decrypt(&payload);
+        }
+        Ok(payload)
     }
-    fn broadcast_with_flags(&self, flags: i32, data: &[u8]) -> ServerResult<()> {
-        let tracker = self.connection_tracker.borrow();
-        let connections = tracker.active_connections();
-        let _res = TransmitterHelper::send_with_iter(&self.socket, connections, flags, data);
+    // `None` if there are no outbound transforms
+    fn transform_outbound(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let callbacks = self.callbacks.borrow();
+        let mut transforms = callbacks.outbound_transforms.iter();
+        let first = transforms.next()?;
+        Some(transforms.fold(first(data), |data, transform| transform(&data)))
+    }
+    // unreliable messages are numbered by the client, reliable ones are never duplicated by GNS
+    fn is_duplicate(&self, client: &Uuid, reliable: bool, sequence: u64) -> bool {
+        let Some(window_size) = self.settings.borrow().dedup_window else {
+            return false;
+        };
+        if reliable {
+            return false;
+        }
+        self.dedup_windows
+            .borrow_mut()
+            .entry(client.clone())
+            .or_insert_with(|| DedupWindow::new(window_size))
+            .is_duplicate(sequence)
+    }
+    // applies the overflow policy when `size` more bytes would exceed the client's outgoing limit,
+    // returns false if the message must be dropped
+    fn fits_outgoing_limit(&self, client: &Uuid, size: usize) -> ServerResult<bool> {
+        let limit = self
+            .outgoing_limits
+            .borrow()
+            .get(client)
+            .copied()
+            .or(self.settings.borrow().outgoing_limit);
+        let Some(limit) = limit else {
+            return Ok(true);
+        };
+        let status = self.connection_info(client)?;
+        let queued = status.pending_reliable + status.pending_unreliable + status.sent_unacked_reliable;
+        let batched: usize = self
+            .pending_batch
+            .borrow()
+            .iter()
+            .filter(|(batched_client, ..)| batched_client == client)
+            .map(|(_client, _connection, _flags, _lane, _priority, data)| data.len())
+            .sum();
+        let mut pending = queued.max(0) as usize + batched;
+        if pending + size <= limit.max_bytes {
+            return Ok(true);
+        }
+        match limit.policy {
+            OverflowPolicy::DropNewest => Ok(false),
+            OverflowPolicy::DropOldest => {
+                // queued messages can't be cancelled, only batched ones are dropped
+                self.pending_batch.borrow_mut().retain(|(batched_client, .., data)| {
+                    if batched_client != client || pending + size <= limit.max_bytes {
+                        return true;
+                    }
+                    pending -= data.len();
+                    false
+                });
+                Ok(pending + size <= limit.max_bytes)
+            }
+            OverflowPolicy::Kick => {
+                #[cfg(feature = "tracing")]
+                tracing::info!(client = %client, max_bytes = limit.max_bytes, "outgoing limit exceeded, kicking");
+                self.kick(client, "Outgoing limit exceeded")?;
+                Ok(false)
+            }
+        }
+    }
+    fn track_client_disconnected(&self, client: &Uuid) {
+        self.connection_tracker
+            .borrow_mut()
+            .track_client_disconnected(client);
+        self.player_data.borrow_mut().remove(client);
+        self.fragments.borrow_mut().remove(client);
+        self.traffic_stats.borrow_mut().remove(client);
+        self.last_received_at.borrow_mut().remove(client);
+        self.pending_connects.borrow_mut().remove(client);
+        self.rate_limiter.borrow_mut().remove(client);
+        self.outgoing_limits.borrow_mut().remove(client);
+        self.sessions.borrow_mut().track_client_disconnected(client);
+        self.dedup_windows.borrow_mut().remove(client);
+        self.ack_senders.borrow_mut().remove(client);
+        self.poor_connections.borrow_mut().remove(client);
+        self.pending_pings
+            .borrow_mut()
+            .retain(|(pinged_client, _ping_id), _sent_at| pinged_client != client);
+    }
+    fn apply_connect_decision(
+        &self,
+        socket: &GnsSocket<IsServer>,
+        client: &Uuid,
+        connection: GnsConnection,
+        decision: ConnectDecision,
+    ) -> ServerResult<()> {
+        match decision {
+            ConnectDecision::Accept => {
+                if socket.accept(connection).is_err() {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(client = %client, "cannot accept connection");
+                    self.track_client_disconnected(client);
+                    return Err(ServerError::Accept);
+                }
+                #[cfg(feature = "tracing")]
+                tracing::info!(client = %client, "connect request accepted");
+            }
+            ConnectDecision::Reject(reason) => {
+                #[cfg(feature = "tracing")]
+                tracing::info!(client = %client, end_reason = reason.end_reason, "connect request rejected");
+                socket.close_connection(connection, reason.end_reason as _, &reason.debug, false);
+                self.track_client_disconnected(client);
+            }
+            ConnectDecision::Pending => {
+                #[cfg(feature = "tracing")]
+                tracing::info!(client = %client, "connect request deferred");
+                self.pending_connects.borrow_mut().insert(client.clone());
+            }
+        }
         Ok(())
     }
+    fn close_client(
+        &self,
+        client: &Uuid,
+        end_reason: i32,
+        reason: &str,
+        linger: bool,
+    ) -> ServerResult<()> {
+        let connection = self
+            .connection_tracker
+            .borrow()
+            .client_connection(client)
+            .ok_or(ServerError::UnknownPlayer)?;
+        let endpoint = self
+            .connection_tracker
+            .borrow()
+            .client_endpoint(client)
+            .cloned()
+            .ok_or(ServerError::UnknownPlayer)?;
 
-    fn create_regular_message(msg_type: i64, data: &[u8]) -> protobuf::Result<Vec<u8>> {
-        let mut payload = GeneralOmgppMessage::new();
-        let mut message = general_omgpp_message::Message::new();
-        message.type_ = msg_type;
-        message.data = Vec::from(data); // somehow get rid of unessesary array copying
-        payload.data = Some(Data::Message(message));
-        let bytes = payload.write_to_bytes()?;
-        return Ok(bytes);
-    }
-    fn create_rpc_message(
+        // locally closed connections are not reported by GNS, so notify about disconnection manually
+        self.socket()
+            .close_connection(connection, end_reason as _, reason, linger);
+        self.track_client_disconnected(client);
+        let reason = DisconnectReason {
+            end_reason,
+            debug: reason.to_string(),
+        };
+        self.notify_connection_changed(client, &endpoint, ConnectionState::Disconnected, Some(&reason));
+        Ok(())
+    }
+    fn sample_connection_quality(&self) {
+        let Some(thresholds) = self.settings.borrow().quality_thresholds else {
+            return;
+        };
+        let now = Instant::now();
+        if let Some(sampled_at) = self.last_quality_sample_at.get() {
+            if now - sampled_at < thresholds.sample_interval {
+                return;
+            }
+        }
+        self.last_quality_sample_at.set(Some(now));
+        let active_clients = self.connection_tracker.borrow().active_clients();
+        for (client, endpoint) in active_clients {
+            let Ok(status) = self.connection_info(&client) else {
+                continue;
+            };
+            let poor = thresholds.is_poor(&status);
+            let was_poor = self.poor_connections.borrow().contains(&client);
+            if poor == was_poor {
+                continue;
+            }
+            match poor {
+                true => self.poor_connections.borrow_mut().insert(client.clone()),
+                false => self.poor_connections.borrow_mut().remove(&client),
+            };
+            self.notify_connection_quality_changed(&client, &endpoint, poor, &status);
+        }
+    }
+    fn notify_connection_quality_changed(
+        &self,
+        client: &Uuid,
+        endpoint: &Endpoint,
+        poor: bool,
+        status: &ConnectionRealTimeStatus,
+    ) {
+        if let Some(queue) = self.event_queue.borrow_mut().as_mut() {
+            queue.push(ServerEvent {
+                sender: client.clone(),
+                endpoint: endpoint.clone(),
+                kind: ServerEventKind::ConnectionQualityChanged { poor },
+            });
+            return;
+        }
+        if let Some(cb) = &self.callbacks.borrow().on_connection_quality_changed_callback {
+            cb(self, client, endpoint, poor, status);
+        }
+    }
+    fn notify_tick(&self) {
+        // taken out for the call, the callback needs `&mut` and may use the server (and its callbacks) itself
+        let Some(mut callback) = self.callbacks.borrow_mut().on_tick_callback.take() else {
+            return;
+        };
+        callback(self);
+        {
+            let mut callbacks = self.callbacks.borrow_mut();
+            // a callback registered by the callback itself wins
+            if callbacks.on_tick_callback.is_none() {
+                callbacks.on_tick_callback = Some(callback);
+            }
+        }
+        // messages batched by the callback go out within the same call, failures are reported only by explicit `flush`
+        _ = self.flush();
+    }
+    fn notify_connection_changed(
+        &self,
+        client: &Uuid,
+        endpoint: &Endpoint,
+        state: ConnectionState,
+        reason: Option<&DisconnectReason>,
+    ) {
+        if let Some(queue) = self.event_queue.borrow_mut().as_mut() {
+            // only final states are reported as events
+            let kind = match (state, reason) {
+                (_, Some(reason)) => ServerEventKind::Disconnected(reason.clone()),
+                (ConnectionState::Connected, None) => ServerEventKind::Connected,
+                (ConnectionState::Reconnected, None) => ServerEventKind::Reconnected,
+                _ => return,
+            };
+            queue.push(ServerEvent {
+                sender: client.clone(),
+                endpoint: endpoint.clone(),
+                kind,
+            });
+            return;
+        }
+        if let Some(cb) = &self.callbacks.borrow().on_connection_changed_callback {
+            cb(self, client, endpoint, state, reason);
+        }
+    }
+    fn notify_message(
+        &self,
+        client: &Uuid,
+        endpoint: &Endpoint,
         reliable: bool,
-        method_id: i64,
-        request_id: u64,
-        arg_type: i64,
-        data: Option<&[u8]>,
-    ) -> protobuf::Result<Vec<u8>> {
-        let mut payload = GeneralOmgppMessage::new();
-        let mut rpc = general_omgpp_message::RpcCall::new();
-        rpc.reliable = reliable;
-        rpc.method_id = method_id;
-        rpc.request_id = request_id;
-        rpc.arg_type = arg_type;
-        rpc.arg_data = match data {
-            Some(byte_array) => Vec::from(byte_array),
-            None => Vec::new(),
+        msg_type: i64,
+        data: Vec<u8>,
+    ) {
+        // clients send no internal regular messages, a misbehaving client must not reach user callbacks
+        if ControlMessage::is_reserved(msg_type) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(client = %client, msg_type, "message with reserved type dropped");
+            return;
+        }
+        let is_filtered = self
+            .callbacks
+            .borrow()
+            .inbound_filters
+            .iter()
+            .any(|filter| !filter(client, msg_type, &data));
+        if is_filtered {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(client = %client, msg_type, "message dropped by inbound filter");
+            return;
+        }
+        if let Some(queue) = self.event_queue.borrow_mut().as_mut() {
+            queue.push(ServerEvent {
+                sender: client.clone(),
+                endpoint: endpoint.clone(),
+                kind: ServerEventKind::Message {
+                    reliable,
+                    msg_type,
+                    data,
+                },
+            });
+            return;
+        }
+        let callbacks = self.callbacks.borrow();
+        if let Some(typed_cb) = callbacks.on_typed_message_callbacks.get(&msg_type) {
+            typed_cb(self, client, endpoint, &data);
+            return;
+        }
+        if let Some(threaded_cb) = &callbacks.on_threaded_message_callback {
+            let (threaded_cb, tasks) = (threaded_cb.clone(), self.tasks.clone());
+            let (sender, endpoint) = (client.clone(), *endpoint);
+            match self.callback_pool.borrow().as_ref() {
+                Some(pool) => pool.dispatch(client, move || {
+                    threaded_cb(&tasks, &sender, &endpoint, reliable, msg_type, data)
+                }),
+                None => threaded_cb(&tasks, &sender, &endpoint, reliable, msg_type, data),
+            }
+            return;
+        }
+        // cb stands for callback
+        if let Some(cb) = &callbacks.on_message_callback {
+            cb(self, client, endpoint, reliable, msg_type, data)
+        }
+    }
+    // decrypts and decompresses the payload of a received regular message, see `decompress` for `max_size`.
+    // Messages which cannot be decompressed are dropped like the ones which cannot be decoded
+    fn open_payload(&self, _client: &Uuid, compression: u32, data: Vec<u8>, max_size: usize) -> Option<Vec<u8>> {
+        match self.decrypt_payload(data).and_then(|data| decompress(compression, data, max_size)) {
+            Ok(data) => Some(data),
+            Err(_err) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(client = %_client, error = %_err, "cannot decrypt or decompress message");
+                None
+            }
+        }
+    }
+    fn notify_rpc(&self, client: &Uuid, endpoint: &Endpoint, rpc_call: RpcCall) {
+        if let Some(queue) = self.event_queue.borrow_mut().as_mut() {
+            queue.push(ServerEvent {
+                sender: client.clone(),
+                endpoint: endpoint.clone(),
+                kind: ServerEventKind::Rpc {
+                    reliable: rpc_call.reliable,
+                    method_id: rpc_call.method_id,
+                    request_id: rpc_call.request_id,
+                    arg_type: rpc_call.arg_type,
+                    arg_data: rpc_call.arg_data,
+                },
+            });
+            return;
+        }
+        if !rpc_call.is_reply && self.rpc_registry.borrow().contains(rpc_call.method_id) {
+            let args = RpcArgs::new(rpc_call.arg_type, rpc_call.arg_data);
+            let reply = self
+                .rpc_registry
+                .borrow()
+                .handle(self, client, endpoint, rpc_call.method_id, args);
+            if let Some(reply) = reply {
+                _ = self.reply_rpc(
+                    client,
+                    rpc_call.reliable,
+                    rpc_call.method_id,
+                    rpc_call.request_id,
+                    reply.arg_type,
+                    Some(&reply.data),
+                );
+            }
+            return;
+        }
+        if let Some(rpc_callback) = &self.callbacks.borrow().on_rpc_callback {
+            rpc_callback(
+                self,
+                client,
+                endpoint,
+                rpc_call.reliable,
+                rpc_call.method_id,
+                rpc_call.request_id,
+                rpc_call.arg_type,
+                rpc_call.arg_data,
+            );
+        }
+    }
+    fn send_to_client(
+        &self,
+        client: &Uuid,
+        connection: GnsConnection,
+        flags: i32,
+        data: &[u8],
+    ) -> ServerResult<()> {
+        let failed = self.send_to_clients(vec![(client.clone(), connection)], flags, data)?;
+        match failed.into_iter().next() {
+            Some((_client, result)) => Err(ServerError::SendFailed(result)),
+            None => Ok(()),
+        }
+    }
+    /// Sends already encoded message to the given clients within a single `send_messages` batch.
+    /// Returns clients for which GNS refused the message
+    fn send_to_clients(
+        &self,
+        targets: impl IntoIterator<Item = (Uuid, GnsConnection)>,
+        flags: i32,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        self.send_to_clients_on_lane(targets, flags, 0, 0, data)
+    }
+    // same as `send_to_clients_on_lane`, also returns number of the targets.
+    // Active clients are iterated with the tracker borrowed, nothing called while sending may modify it
+    fn send_to_targets_on_lane(
+        &self,
+        targets: Targets,
+        flags: i32,
+        lane: u16,
+        priority: u8,
+        data: &[u8],
+    ) -> ServerResult<(usize, Vec<(Uuid, EResult)>)> {
+        let mut target_count = 0;
+        let failed = match targets {
+            Targets::Clients(clients) => {
+                let clients = clients.into_iter().inspect(|_target| target_count += 1);
+                self.send_to_clients_on_lane(clients, flags, lane, priority, data)?
+            }
+            Targets::Active(filter) => {
+                let connection_tracker = self.connection_tracker.borrow();
+                let active = connection_tracker
+                    .active_client_connections()
+                    .filter(|(client, _connection)| filter(client))
+                    .inspect(|_target| target_count += 1);
+                self.send_to_clients_on_lane(active, flags, lane, priority, data)?
+            }
         };
-        payload.data = Some(Data::Rpc(rpc));
-        let bytes = payload.write_to_bytes()?;
-        return Ok(bytes);
+        Ok((target_count, failed))
     }
+    // `priority` orders messages queued while batching is enabled
+    fn send_to_clients_on_lane(
+        &self,
+        targets: impl IntoIterator<Item = (Uuid, GnsConnection)>,
+        flags: i32,
+        lane: u16,
+        priority: u8,
+        data: &[u8],
+    ) -> ServerResult<Vec<(Uuid, EResult)>> {
+        // check before allocation, GNS fails oversized messages without telling why
+        if data.len() > MAX_MESSAGE_SIZE {
+            return Err(ServerError::MessageTooLarge {
+                size: data.len(),
+                max: MAX_MESSAGE_SIZE,
+            });
+        }
+        if self.settings.borrow().batching {
+            self.pending_batch.borrow_mut().extend(
+                targets
+                    .into_iter()
+                    .map(|(client, connection)| (client, connection, flags, lane, priority, Vec::from(data))),
+            );
+            return Ok(Vec::new());
+        }
+        // every allocated message holds a copy of the data, chunks bound how many copies exist at once.
+        // Targets are taken from the iterator chunk by chunk into the same buffer
+        let chunk_size = self
+            .settings
+            .borrow()
+            .send_chunk_size
+            .unwrap_or(usize::MAX)
+            .max(1);
+        let mut targets = targets.into_iter().peekable();
+        let mut chunk = Vec::new();
+        let mut failed = Vec::new();
+        while targets.peek().is_some() {
+            chunk.clear();
+            chunk.extend(targets.by_ref().take(chunk_size));
+            let connections = chunk.iter().map(|(_client, connection)| connection.clone());
+            let send_results =
+                TransmitterHelper::send_on_lane(self.socket(), connections, flags, lane, data);
+            for ((client, _connection), result) in chunk.iter().zip(send_results) {
+                match result.right() {
+                    Some(err) => failed.push((client.clone(), err)),
+                    None => self.record_sent(client.clone(), data.len()),
+                }
+            }
+        }
+        Ok(failed)
+    }
+}
 
-    fn create_cmd_message(
-        cmd: String,
-        request_id: u64,
-        args: Vec<String>,
-    ) -> protobuf::Result<Vec<u8>> {
-        let mut payload = GeneralOmgppMessage::new();
-        let mut request = general_omgpp_message::CmdRequest::new();
-        request.cmd = cmd;
-        request.request_id = request_id;
-        request.args = args;
-        payload.data = Some(Data::Cmd(request));
-        let bytes = payload.write_to_bytes()?;
-        return Ok(bytes);
+/// Flushes batched messages and closes every connection with linger, so reliable messages
+/// already queued are still delivered after the server is gone. Callbacks are not invoked
+impl<'a> Drop for Server<'a> {
+    fn drop(&mut self) {
+        _ = self.flush();
+        let clients = self.connection_tracker.borrow().tracked_clients();
+        for client in clients.iter() {
+            let Some(connection) = self.connection_tracker.borrow().client_connection(client) else {
+                continue;
+            };
+            self.socket().close_connection(
+                connection,
+                END_REASON_APP_GENERIC as _,
+                "Server is shutting down",
+                true,
+            );
+        }
     }
 }
 
 impl<'a> Debug for Server<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Server")
-            .field("ip", &self.ip)
-            .field("port", &self.port)
+            .field("local_addrs", &self.local_addrs)
             .field("connection_tracker", &self.connection_tracker)
             .finish()
     }
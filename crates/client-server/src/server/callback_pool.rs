@@ -0,0 +1,78 @@
+//! Worker threads running message callbacks off the thread calling `Server::process`
+//! (see `Server::set_callback_workers`).
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use uuid::Uuid;
+
+use super::Server;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+pub(crate) type ServerTask = Box<dyn FnOnce(&Server) + Send + 'static>;
+
+/// Lets callbacks running on worker threads use the server: tasks are run on the thread calling
+/// `Server::process`, at the beginning of its next call
+#[derive(Clone)]
+pub struct ServerTasks {
+    tasks: Sender<ServerTask>,
+}
+
+impl ServerTasks {
+    pub(crate) fn channel() -> (ServerTasks, Receiver<ServerTask>) {
+        let (tasks, receiver) = mpsc::channel();
+        (ServerTasks { tasks }, receiver)
+    }
+    /// Runs `task` on the server thread, e.g. to reply to the message. Dropped if the server is gone
+    pub fn execute(&self, task: impl FnOnce(&Server) + Send + 'static) {
+        _ = self.tasks.send(Box::new(task));
+    }
+}
+
+/// Fixed number of workers, each with its own queue. Jobs of a client always go to the same worker,
+/// so they run in the order they were dispatched
+pub struct CallbackPool {
+    queues: Vec<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl CallbackPool {
+    pub fn new(worker_count: usize) -> CallbackPool {
+        let (queues, workers) = (0..worker_count.max(1))
+            .map(|_worker| {
+                let (queue, jobs) = mpsc::channel::<Job>();
+                // runs until the pool drops its queue. A panicking callback only loses its own job,
+                // the worker keeps running the jobs queued after it
+                let worker = thread::spawn(move || {
+                    for job in jobs {
+                        if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                            #[cfg(feature = "tracing")]
+                            tracing::error!("threaded message callback panicked");
+                        }
+                    }
+                });
+                (queue, worker)
+            })
+            .unzip();
+        CallbackPool { queues, workers }
+    }
+    pub fn worker_count(&self) -> usize {
+        self.queues.len()
+    }
+    pub fn dispatch(&self, client: &Uuid, job: impl FnOnce() + Send + 'static) {
+        let worker = (client.as_u128() % self.queues.len() as u128) as usize;
+        _ = self.queues[worker].send(Box::new(job));
+    }
+}
+
+/// Waits for the workers to run the jobs already dispatched
+impl Drop for CallbackPool {
+    fn drop(&mut self) {
+        self.queues.clear();
+        for worker in self.workers.drain(..) {
+            _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,41 @@
+/// Result of `on_connect_requested` callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectDecision {
+    Accept,
+    Reject(RejectReason),
+    /// Client stays in `Connecting` until `Server::resolve_pending` is called.
+    /// GNS closes the connection by itself if it is not resolved within its initial timeout
+    Pending,
+}
+
+/// End code and message the rejected client receives.
+/// `end_reason` should be one of `ESteamNetConnectionEnd` application codes (1000..=1999)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectReason {
+    pub end_reason: i32,
+    pub debug: String,
+}
+
+impl RejectReason {
+    pub fn new(end_reason: i32, debug: &str) -> RejectReason {
+        RejectReason {
+            end_reason,
+            debug: debug.to_string(),
+        }
+    }
+}
+
+impl Default for RejectReason {
+    fn default() -> Self {
+        RejectReason::new(0, "You are not allowed to connect") // k_ESteamNetConnectionEnd_Invalid
+    }
+}
+
+impl From<bool> for ConnectDecision {
+    fn from(accept: bool) -> Self {
+        match accept {
+            true => ConnectDecision::Accept,
+            false => ConnectDecision::Reject(RejectReason::default()),
+        }
+    }
+}
@@ -7,13 +7,26 @@ use std::time::Duration;
 use uuid::Uuid;
 
 
+/// Defines how uuid of a new connection is generated
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UuidScheme {
+    /// Each connection gets a fresh random uuid, so a reconnecting client is treated as a new one
+    #[default]
+    PerConnection,
+    /// Uuid is derived from the remote address and port. Stays the same across reconnections,
+    /// but clients sharing the same address and port (e.g. behind NAT) collide
+    AddressBased,
+}
+
 #[derive(Default, Debug)]
 pub struct ConnectionTracker {
     connections: BiHashMap<Uuid, GnsConnection>,
     unverified_connections: HashMap<Uuid, Instant>,
     endpoints: BiHashMap<Uuid, Endpoint>,
     states: HashMap<Uuid,ConnectionState>,
-    unverified_connection_expire_period: Duration
+    listeners: HashMap<Uuid, usize>,
+    unverified_connection_expire_period: Duration,
+    uuid_scheme: UuidScheme,
 }
 
 impl ConnectionTracker {
@@ -23,16 +36,36 @@ impl ConnectionTracker {
             ..Default::default()
         }
     }
+    pub fn set_unverified_connection_expire_period(&mut self, period: Duration) {
+        self.unverified_connection_expire_period = period;
+    }
+    pub fn set_uuid_scheme(&mut self, scheme: UuidScheme) {
+        self.uuid_scheme = scheme;
+    }
     pub fn active_clients(&self) -> Vec<(Uuid, Endpoint)> {
         let endpoints = &self.endpoints;
         let active_endpoints = endpoints
             .into_iter()
-            .filter(|item| !self.unverified_connections.contains_key(item.0))
+            .filter(|item| self.is_verified(item.0))
             .map(|item| (item.0.clone(), item.1.clone()))
             .collect();
         active_endpoints
     }
 
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+    /// Number of tracked connections from `ip`, regardless of the remote port
+    pub fn connection_count_from(&self, ip: &IpAddr) -> usize {
+        self.endpoints
+            .iter()
+            .filter(|(_uuid, endpoint)| endpoint.ip == *ip)
+            .count()
+    }
+    pub fn tracked_clients(&self) -> Vec<Uuid> {
+        self.connections.left_values().cloned().collect()
+    }
+
     pub fn client_connection(&self, client: &Uuid) -> Option<GnsConnection> {
         self.connections
             .get_by_left(client)
@@ -49,6 +82,9 @@ impl ConnectionTracker {
             .get_by_left(client)
             .map(|conn| conn)
     }
+    pub fn client_listener(&self, client: &Uuid) -> Option<usize> {
+        self.listeners.get(client).cloned()
+    }
     pub fn track_client_disconnected(&mut self, uuid: &Uuid) {
         if self.connections.contains_left(uuid) {
             self.connections.remove_by_left(uuid);
@@ -59,17 +95,22 @@ impl ConnectionTracker {
         if self.unverified_connections.contains_key(uuid){
             self.unverified_connections.remove(uuid);
         }
-        //TODO remove disconnected entries after some period; Prevent infinite collection growing
-        self.states.insert(uuid.clone(), ConnectionState::Disconnected);
+        self.listeners.remove(uuid);
+        // disconnected clients are forgotten, their state is reported as `ConnectionState::None` afterwards
+        self.states.remove(uuid);
     }
 
+    pub fn track_client_connecting(&mut self, uuid: Uuid, endpoint:Endpoint,connection: GnsConnection, listener: usize) {
+        self.connections.insert(uuid, connection);
+        self.endpoints.insert(uuid, endpoint);
+        self.listeners.insert(uuid, listener);
+        self.states.insert(uuid.clone(), ConnectionState::Connecting);
+    }
     pub fn track_client_connected_unverified(&mut self, uuid: Uuid, endpoint:Endpoint,connection: GnsConnection) {
         if !self.connections.contains_left(&uuid){
             self.connections.insert(uuid,connection);
         }
-        let now = Instant::now();
-        println!("{:?} - {:?}",uuid,now);
-        self.unverified_connections.insert(uuid, now);
+        self.unverified_connections.insert(uuid, Instant::now());
         // TODO decide what todo when we have already associated endpoint
         let _old_endpoint = self.endpoints.insert(uuid, endpoint);   
         self.states.insert(uuid.clone(), ConnectionState::ConnectedUnverified);
@@ -85,6 +126,9 @@ impl ConnectionTracker {
         let _old_endpoint = self.endpoints.insert(uuid, endpoint);   
         self.states.insert(uuid.clone(), ConnectionState::Connected);
     }
+    fn is_verified(&self, client: &Uuid) -> bool {
+        self.state(client) == ConnectionState::Connected
+    }
     pub fn client_by_connection(&self, connection: &GnsConnection) -> Option<&Uuid> {
         self.connections.get_by_right(connection)
     }
@@ -92,10 +136,17 @@ impl ConnectionTracker {
         let connections = &self.connections;
         connections
             .into_iter()
-            .filter(|item| !self.unverified_connections.contains_key(item.0))
+            .filter(|item| self.is_verified(item.0))
             .map(|item| item.1.clone())
             .into_iter()
     }
+    pub fn active_client_connections(&self) -> impl Iterator<Item = (Uuid, GnsConnection)> + '_ {
+        let connections = &self.connections;
+        connections
+            .into_iter()
+            .filter(|item| self.is_verified(item.0))
+            .map(|item| (item.0.clone(), item.1.clone()))
+    }
     pub fn expired_unverified_connections(&self) ->impl Iterator<Item = GnsConnection> + '_ {
         let now = Instant::now();
         let expiring_period =self.unverified_connection_expire_period.clone();
@@ -110,6 +161,12 @@ impl ConnectionTracker {
             .filter(|item| item.is_some())
             .map(|item| item.unwrap())
     }
+    pub fn generate_client_uuid(&self, endpoint: &Endpoint) -> Uuid {
+        match self.uuid_scheme {
+            UuidScheme::PerConnection => Uuid::new_v4(),
+            UuidScheme::AddressBased => ConnectionTracker::generate_endpoint_uuid(endpoint),
+        }
+    }
     pub fn generate_endpoint_uuid(endpoint: &Endpoint) -> Uuid {
         ConnectionTracker::generate_uuid(endpoint.ip, endpoint.port)
     }
@@ -0,0 +1,84 @@
+//! State transitions of connections handled by `Server::process`. GNS connection events and synthetic ones
+//! (see `Server::inject_connection_event`) go through the same state machine.
+use gns::{GnsConnection, GnsConnectionEvent, GnsConnectionInfo};
+use gns_sys::ESteamNetworkingConnectionState;
+use omgpp_core::{Endpoint, ToEndpoint};
+
+pub(crate) struct ConnectionTransition {
+    connection: GnsConnection,
+    old_state: ESteamNetworkingConnectionState,
+    new_state: ESteamNetworkingConnectionState,
+    endpoint: Endpoint,
+    end_reason: i32,
+    end_debug: String,
+    // GNS info of the connection, synthetic transitions have none
+    info: Option<GnsConnectionInfo>,
+}
+
+impl ConnectionTransition {
+    pub(crate) fn from_event(event: &GnsConnectionEvent) -> ConnectionTransition {
+        let info = event.info();
+        ConnectionTransition {
+            connection: event.connection(),
+            old_state: event.old_state(),
+            new_state: info.state(),
+            endpoint: info.to_endpoint(),
+            end_reason: info.end_reason() as _,
+            end_debug: info.end_debug().to_string(),
+            info: Some(info),
+        }
+    }
+    #[cfg(feature = "test-util")]
+    pub(crate) fn synthetic(
+        connection: GnsConnection,
+        endpoint: Endpoint,
+        old_state: ESteamNetworkingConnectionState,
+        new_state: ESteamNetworkingConnectionState,
+        end_reason: i32,
+        end_debug: String,
+    ) -> ConnectionTransition {
+        ConnectionTransition {
+            connection,
+            old_state,
+            new_state,
+            endpoint,
+            end_reason,
+            end_debug,
+            info: None,
+        }
+    }
+    pub(crate) fn connection(&self) -> GnsConnection {
+        self.connection.clone()
+    }
+    pub(crate) fn old_state(&self) -> ESteamNetworkingConnectionState {
+        self.old_state
+    }
+    pub(crate) fn new_state(&self) -> ESteamNetworkingConnectionState {
+        self.new_state
+    }
+    pub(crate) fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+    pub(crate) fn end_reason(&self) -> i32 {
+        self.end_reason
+    }
+    pub(crate) fn end_debug(&self) -> &str {
+        &self.end_debug
+    }
+    pub(crate) fn info(&self) -> Option<&GnsConnectionInfo> {
+        self.info.as_ref()
+    }
+    /// Connection closed by the client or lost, handled by the disconnect arm of `process_connection_events`
+    pub(crate) fn is_disconnect(&self) -> bool {
+        matches!(
+            (self.old_state, self.new_state),
+            (
+                ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connecting
+                    | ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
+                ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ClosedByPeer
+                    | ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_None
+                    | ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ProblemDetectedLocally,
+            )
+        )
+    }
+}
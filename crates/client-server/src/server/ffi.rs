@@ -1,109 +1,552 @@
 use omgpp_core::{
-    ffi::{EndpointFFI, ToFfi, UuidFFI},
-    ConnectionState,
+    ffi::{slice_from_ffi_ptr, EndpointFFI, ToFfi, UuidFFI},
+    ConnectionRealTimeStatus, ConnectionState, Endpoint, LaneConfig, ProcessStats, TrafficStats, TypeStats,
 };
 use std::{
+    cell::Cell,
     ffi::{c_char, c_uchar, CStr},
     net::IpAddr,
+    panic::{self, AssertUnwindSafe},
     ptr::null_mut,
     str::FromStr,
+    time::Duration,
 };
 use uuid::Uuid;
-use crate::server::Server;
+use crate::server::{
+    connect_decision::ConnectDecision,
+    outgoing_limit::{OutgoingLimit, OverflowPolicy},
+    rate_limiter::{RateLimit, RateLimitPolicy},
+    send_outcome::SendOutcome,
+    server_error::ServerError,
+    server_settings::{PollOrder, QualityThresholds},
+    Server, ServerResult,
+};
 
 
 // FFI
 type ServerOnConnectRequested = extern "C" fn(UuidFFI, EndpointFFI) -> bool;
-type ServerOnConnectionChanged = extern "C" fn(UuidFFI, EndpointFFI, ConnectionState);
-type ServerOnMessage = extern "C" fn(UuidFFI, EndpointFFI, i64, *const c_uchar, usize);
+type ServerOnConnectDeferred = extern "C" fn(UuidFFI, EndpointFFI);
+type ServerOnConnectionChanged = extern "C" fn(UuidFFI, EndpointFFI, ConnectionState, i32);
+type ServerOnMessage = extern "C" fn(UuidFFI, EndpointFFI, bool, i64, *const c_uchar, usize);
 type ServerOnRpc = extern "C" fn(UuidFFI, EndpointFFI,bool, i64, u64, i64, *const c_uchar,usize);
+type ServerOnPong = extern "C" fn(UuidFFI, EndpointFFI, u64); // round-trip time in microseconds
+type ServerOnTick = extern "C" fn();
+type ServerClientPredicate = extern "C" fn(UuidFFI) -> bool;
+type ServerOnConnectionQualityChanged =
+    extern "C" fn(UuidFFI, EndpointFFI, bool, ConnectionRealTimeStatus);
+type ServerOnAuthenticate = extern "C" fn(UuidFFI, EndpointFFI, *const c_uchar, usize) -> bool; // first auth arg as UTF-8
 
-#[no_mangle]
-pub unsafe extern "C" fn server_create(ip: *const c_char, port: u16) -> *mut Server<'static> {
-    let c_string = CStr::from_ptr(ip).to_str();
-    if c_string.is_err() {
-        return null_mut();
-    }
+/// Returned by every server FFI function, `server_create` reports it through `server_last_error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum ServerFfiError {
+    None = 0,
+    NullPointer = 1,
+    InvalidEncoding = 2, // string is not valid UTF-8
+    InvalidAddress = 3,  // string is not an IP address
+    UnknownPlayer = 4,
+    SendFailed = 5,      // message cannot be encoded or sent
+    NotFound = 6,        // e.g. unbanned address was not banned
+    Failed = 7,          // any other server error
+    Dropped = 8,         // unreliable message was dropped, too much data is queued
+    Panicked = 9,        // e.g. a callback called back into the server while it was busy; destroy the server
+}
 
-    if let Some(addres) = IpAddr::from_str(c_string.unwrap()).ok() {
-        let server_res = Server::new(addres, port);
-        match server_res {
-            Ok(server) => Box::into_raw(Box::from(server)),
-            Err(_) => null_mut(),
-        }
-    } else {
-        null_mut()
+impl From<&ServerError> for ServerFfiError {
+    fn from(error: &ServerError) -> Self {
+        match error {
+            ServerError::UnknownPlayer | ServerError::UnknownConnection => {
+                ServerFfiError::UnknownPlayer
+            }
+            ServerError::MessageEncode
+            | ServerError::SendFailed(_)
+            | ServerError::MessageTooLarge { .. }
+            | ServerError::ReservedMessageType(_) => ServerFfiError::SendFailed,
+            _ => ServerFfiError::Failed,
+        }
+    }
+}
+impl<T> From<ServerResult<T>> for ServerFfiError {
+    fn from(result: ServerResult<T>) -> Self {
+        match result {
+            Ok(_) => ServerFfiError::None,
+            Err(error) => ServerFfiError::from(&error),
+        }
     }
 }
 
+thread_local! {
+    static LAST_ERROR: Cell<ServerFfiError> = const { Cell::new(ServerFfiError::None) };
+}
+
+// sets and returns the last error
+fn set_last_error(error: ServerFfiError) -> ServerFfiError {
+    LAST_ERROR.with(|last_error| last_error.set(error));
+    error
+}
+
+// every FFI function runs its body through it, a panic must not unwind into the caller.
+// The returned error, `Panicked` included, is recorded as the last error
+fn catch_panic(body: impl FnOnce() -> ServerFfiError) -> ServerFfiError {
+    let error = panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(ServerFfiError::Panicked);
+    set_last_error(error)
+}
+
+/// Error of the last call made on the current thread, e.g. why `server_create` returned null
+#[no_mangle]
+pub extern "C" fn server_last_error() -> ServerFfiError {
+    LAST_ERROR.with(|last_error| last_error.get())
+}
+
+/// Re-attempts GNS initialization after `server_create` (or `client_connect`) failed because GNS was not available
 #[no_mangle]
-pub unsafe extern "C" fn server_process(server: *mut Server) {
-    _ = server.as_mut().unwrap().process::<128>();
+pub extern "C" fn server_gns_reinit() -> ServerFfiError {
+    catch_panic(|| {
+        match omgpp_core::gns_reinit() {
+            Ok(_) => ServerFfiError::None,
+            Err(_) => ServerFfiError::Failed,
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn server_create(ip: *const c_char, port: u16) -> *mut Server<'static> {
+    let created = panic::catch_unwind(|| match ip_from_ffi_ptr(ip) {
+        Ok(address) => match Server::new(address, port) {
+            Ok(server) => (ServerFfiError::None, Box::into_raw(Box::from(server))),
+            Err(error) => (ServerFfiError::from(&error), null_mut()),
+        },
+        Err(error) => (error, null_mut()),
+    });
+    let (error, server) = created.unwrap_or((ServerFfiError::Panicked, null_mut()));
+    set_last_error(error);
+    server
+}
+
+/// `out_stats` (may be null) receives number of handled events and messages
+#[no_mangle]
+pub unsafe extern "C" fn server_process(
+    server: *mut Server,
+    out_stats: *mut ProcessStats,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        let result = server.process::<128>();
+        if let (Ok(stats), false) = (&result, out_stats.is_null()) {
+            *out_stats = *stats;
+        }
+        result.into()
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn server_register_on_connect_requested(
     server: *mut Server,
     callback: ServerOnConnectRequested,
-) {
-    server
-        .as_mut()
-        .unwrap()
-        .register_on_connect_requested(move |_server,uuid, endpoint| {
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_connect_requested(move |_server, uuid, endpoint| {
             callback(uuid.to_ffi(), endpoint.to_ffi())
         });
+        ServerFfiError::None
+    })
 }
 
+/// Every connect request is deferred, `callback` is only notified about it.
+/// Each request must be answered later with `server_resolve_pending`
+#[no_mangle]
+pub unsafe extern "C" fn server_register_on_connect_requested_deferred(
+    server: *mut Server,
+    callback: ServerOnConnectDeferred,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_connect_requested(move |_server, uuid, endpoint| {
+            callback(uuid.to_ffi(), endpoint.to_ffi());
+            ConnectDecision::Pending
+        });
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_resolve_pending(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    accept: bool,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid)) = (server.as_ref(), uuid_from_ffi_ptr(uuid)) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.resolve_pending(&client_uuid, accept).into()
+    })
+}
 #[no_mangle]
 pub unsafe extern "C" fn server_register_on_connection_state_change(
     server: *mut Server,
     callback: ServerOnConnectionChanged,
-) {
-    server
-        .as_mut()
-        .unwrap()
-        .register_on_connection_state_changed(move |_server, uuid, endpoint, state| {
-            callback(uuid.to_ffi(), endpoint.to_ffi(), state)
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_connection_state_changed(move |_server, uuid, endpoint, state, reason| {
+            let end_reason = reason.map(|reason| reason.end_reason).unwrap_or(0);
+            callback(uuid.to_ffi(), endpoint.to_ffi(), state, end_reason)
         });
+        ServerFfiError::None
+    })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn server_register_on_message(
     server: *mut Server,
     callback: ServerOnMessage,
-) {
-    server
-        .as_mut()
-        .unwrap()
-        .register_on_message(move |_server,uuid, endpoint, message_id, data| {
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_message(move |_server, uuid, endpoint, reliable, message_id, data| {
             callback(
                 uuid.to_ffi(),
                 endpoint.to_ffi(),
+                reliable,
                 message_id,
                 data.as_ptr(),
                 data.len(),
             )
         });
+        ServerFfiError::None
+    })
 }
+/// The callback is invoked on worker threads (see `server_set_callback_workers`)
+/// and must not call server functions, the server is not thread safe
 #[no_mangle]
-pub unsafe extern "C" fn server_register_on_rpc(
+pub unsafe extern "C" fn server_register_on_message_threaded(
     server: *mut Server,
-    callback: ServerOnRpc,
-) {
-    server
-        .as_mut()
-        .unwrap()
-        .register_on_rpc(move |_server,uuid, endpoint, reliable, method_id, request_id, arg_type, arg_data| {
+    callback: ServerOnMessage,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_message_threaded(move |_tasks, uuid, endpoint, reliable, message_id, data| {
             callback(
                 uuid.to_ffi(),
                 endpoint.to_ffi(),
                 reliable,
-                method_id,
-                request_id,
-                arg_type,
-                arg_data.as_ptr(),
-                arg_data.len(),
+                message_id,
+                data.as_ptr(),
+                data.len(),
             )
         });
+        ServerFfiError::None
+    })
+}
+/// `workers == 0` runs threaded message callbacks within `server_process`
+#[no_mangle]
+pub unsafe extern "C" fn server_set_callback_workers(server: *mut Server, workers: usize) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_callback_workers(workers);
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_register_on_rpc(
+    server: *mut Server,
+    callback: ServerOnRpc,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_rpc(
+            move |_server, uuid, endpoint, reliable, method_id, request_id, arg_type, arg_data| {
+                callback(
+                    uuid.to_ffi(),
+                    endpoint.to_ffi(),
+                    reliable,
+                    method_id,
+                    request_id,
+                    arg_type,
+                    arg_data.as_ptr(),
+                    arg_data.len(),
+                )
+            },
+        );
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_register_on_pong(
+    server: *mut Server,
+    callback: ServerOnPong,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_pong(move |_server, uuid, endpoint, rtt| {
+            callback(uuid.to_ffi(), endpoint.to_ffi(), rtt.as_micros() as u64)
+        });
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_register_on_tick(server: *mut Server, callback: ServerOnTick) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_tick(move |_server| callback());
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_register_on_connection_quality_changed(
+    server: *mut Server,
+    callback: ServerOnConnectionQualityChanged,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_connection_quality_changed(move |_server, uuid, endpoint, poor, status| {
+            callback(uuid.to_ffi(), endpoint.to_ffi(), poor, *status)
+        });
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_connection_quality_changed(
+    server: *mut Server,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_connection_quality_changed();
+        ServerFfiError::None
+    })
+}
+/// `max_packet_loss` is a fraction `0.0..=1.0`. Zero `sample_interval_ms` disables quality sampling
+#[no_mangle]
+pub unsafe extern "C" fn server_set_quality_thresholds(
+    server: *mut Server,
+    max_ping_ms: i32,
+    max_packet_loss: f32,
+    sample_interval_ms: u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        let thresholds = match sample_interval_ms {
+            0 => None,
+            _ => Some(QualityThresholds {
+                max_ping_ms,
+                max_packet_loss,
+                sample_interval: Duration::from_millis(sample_interval_ms),
+            }),
+        };
+        server.set_quality_thresholds(thresholds);
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_register_on_authenticate(
+    server: *mut Server,
+    callback: ServerOnAuthenticate,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.register_on_authenticate(move |_server, uuid, endpoint, args| {
+            let token = args.first().map(|token| token.as_bytes()).unwrap_or_default();
+            callback(uuid.to_ffi(), endpoint.to_ffi(), token.as_ptr(), token.len())
+        });
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_set_auth_timeout(
+    server: *mut Server,
+    timeout_ms: u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_auth_timeout(Duration::from_millis(timeout_ms));
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_set_session_expire_period(
+    server: *mut Server,
+    period_ms: u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_session_expire_period(Duration::from_millis(period_ms));
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_end_session(server: *mut Server, uuid: *const UuidFFI) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid)) = (server.as_ref(), uuid_from_ffi_ptr(uuid)) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.end_session(&client_uuid);
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_set_connection_timeout(
+    server: *mut Server,
+    timeout_ms: u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .set_connection_timeout(Duration::from_millis(timeout_ms))
+            .into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_set_ack_resend_timeout(
+    server: *mut Server,
+    timeout_ms: u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_ack_resend_timeout(Duration::from_millis(timeout_ms));
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_connection_timeout(
+    server: *mut Server,
+    out_timeout_ms: *mut u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(out_timeout_ms)) = (server.as_ref(), out_timeout_ms.as_mut()) else {
+            return ServerFfiError::NullPointer;
+        };
+        *out_timeout_ms = server.connection_timeout().as_millis() as u64;
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_authenticate(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_authenticate();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_connect_requested(
+    server: *mut Server,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_connect_requested();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_connection_state_change(
+    server: *mut Server,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_connection_state_changed();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_message(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_message();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_message_threaded(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_message_threaded();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_rpc(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_rpc();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_pong(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_pong();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_unregister_on_tick(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.unregister_on_tick();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_ping(server: *mut Server, uuid: *const UuidFFI) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid)) = (server.as_ref(), uuid_from_ffi_ptr(uuid)) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.ping(&client_uuid).into()
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn server_send(
@@ -113,14 +556,47 @@ pub unsafe extern "C" fn server_send(
     data: *const c_uchar,
     offset: isize,
     size: usize,
-) {
-
-    let msg_data = core::slice::from_raw_parts(data.offset(offset), size);
-    let client_uuid = uuid_from_ffi_ptr(uuid);
-    _ = server
-        .as_ref()
-        .unwrap()
-        .send(&client_uuid, msg_type, msg_data)
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(uuid),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        match server.send(&client_uuid, msg_type, msg_data) {
+            Ok(SendOutcome::Queued) => ServerFfiError::None,
+            Ok(SendOutcome::Dropped) => ServerFfiError::Dropped,
+            Ok(SendOutcome::Failed(_)) => ServerFfiError::SendFailed,
+            Err(error) => ServerFfiError::from(&error),
+        }
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_send_unreliable_acked(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(uuid),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        match server.send_unreliable_acked(&client_uuid, msg_type, msg_data) {
+            Ok(SendOutcome::Queued) => ServerFfiError::None,
+            Ok(SendOutcome::Dropped) => ServerFfiError::Dropped,
+            Ok(SendOutcome::Failed(_)) => ServerFfiError::SendFailed,
+            Err(error) => ServerFfiError::from(&error),
+        }
+    })
 }
 
 #[no_mangle]
@@ -131,13 +607,86 @@ pub unsafe extern "C" fn server_send_reliable(
     data: *const c_uchar,
     offset: isize,
     size: usize,
-) {
-    let msg_data = core::slice::from_raw_parts(data.offset(offset), size);
-    let client_uuid = uuid_from_ffi_ptr(uuid);
-    _ = server
-        .as_ref()
-        .unwrap()
-        .send_reliable(&client_uuid, msg_type, msg_data)
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(uuid),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.send_reliable(&client_uuid, msg_type, msg_data).into()
+    })
+}
+/// Higher `priority` is submitted first within a batch, see `Server::send_reliable_with_priority`
+#[no_mangle]
+pub unsafe extern "C" fn server_send_reliable_with_priority(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+    priority: u8,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(uuid),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .send_reliable_with_priority(&client_uuid, msg_type, msg_data, priority)
+            .into()
+    })
+}
+/// Per-client send failures are not reported
+#[no_mangle]
+pub unsafe extern "C" fn server_send_to_many(
+    server: *mut Server,
+    uuids: *const UuidFFI,
+    uuids_count: usize,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(clients), Some(msg_data)) = (
+            server.as_ref(),
+            uuids_from_ffi_ptr(uuids, uuids_count),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.send_to_many(&clients, msg_type, msg_data).into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_send_to_many_reliable(
+    server: *mut Server,
+    uuids: *const UuidFFI,
+    uuids_count: usize,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(clients), Some(msg_data)) = (
+            server.as_ref(),
+            uuids_from_ffi_ptr(uuids, uuids_count),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .send_to_many_reliable(&clients, msg_type, msg_data)
+            .into()
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn server_broadcast(
@@ -146,9 +695,14 @@ pub unsafe extern "C" fn server_broadcast(
     data: *const c_uchar,
     offset: isize,
     size: usize,
-) {
-    let msg_data = core::slice::from_raw_parts(data.offset(offset), size);
-    _ = server.as_ref().unwrap().broadcast(msg_type, msg_data)
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(msg_data)) = (server.as_ref(), slice_from_ffi_ptr(data, offset, size))
+        else {
+            return ServerFfiError::NullPointer;
+        };
+        server.broadcast(msg_type, msg_data).into()
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn server_broadcast_reliable(
@@ -157,12 +711,60 @@ pub unsafe extern "C" fn server_broadcast_reliable(
     data: *const c_uchar,
     offset: isize,
     size: usize,
-) {
-    let msg_data = core::slice::from_raw_parts(data.offset(offset), size);
-    _ = server
-        .as_ref()
-        .unwrap()
-        .broadcast_reliable(msg_type, msg_data)
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(msg_data)) = (server.as_ref(), slice_from_ffi_ptr(data, offset, size))
+        else {
+            return ServerFfiError::NullPointer;
+        };
+        server.broadcast_reliable(msg_type, msg_data).into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_broadcast_except(
+    server: *mut Server,
+    exclude: *const UuidFFI,
+    exclude_count: usize,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(excluded_clients), Some(msg_data)) = (
+            server.as_ref(),
+            uuids_from_ffi_ptr(exclude, exclude_count),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .broadcast_except(&excluded_clients, msg_type, msg_data)
+            .into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_broadcast_except_reliable(
+    server: *mut Server,
+    exclude: *const UuidFFI,
+    exclude_count: usize,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(excluded_clients), Some(msg_data)) = (
+            server.as_ref(),
+            uuids_from_ffi_ptr(exclude, exclude_count),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .broadcast_except_reliable(&excluded_clients, msg_type, msg_data)
+            .into()
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn server_call_rpc(
@@ -175,20 +777,23 @@ pub unsafe extern "C" fn server_call_rpc(
     arg_data: *const c_uchar,
     arg_data_offset: isize,
     arg_data_size: usize,
-) {
-    let client_uuid = uuid_from_ffi_ptr(client);
-    let msg_data = match arg_data_size {
-        0 => None,
-        _ => Some(core::slice::from_raw_parts(arg_data.offset(arg_data_offset), arg_data_size)),
-    };
-    _ = server.as_ref().unwrap().call_rpc(
-        &client_uuid,
-        reliable,
-        method_id,
-        request_id,
-        arg_type,
-        msg_data,
-    );
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(client),
+            slice_from_ffi_ptr(arg_data, arg_data_offset, arg_data_size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        let msg_data = match arg_data_size {
+            0 => None,
+            _ => Some(msg_data),
+        };
+        server
+            .call_rpc(&client_uuid, reliable, method_id, request_id, arg_type, msg_data)
+            .into()
+    })
 }
 #[no_mangle]
 pub unsafe extern "C" fn server_call_rpc_broadcast(
@@ -200,38 +805,476 @@ pub unsafe extern "C" fn server_call_rpc_broadcast(
     arg_data: *const c_uchar,
     arg_data_offset: isize,
     arg_data_size: usize,
-) {
-    let msg_data = match arg_data_size {
-        0 => None,
-        _ => Some(core::slice::from_raw_parts(arg_data.offset(arg_data_offset), arg_data_size)),
-    };
-    _ = server.as_ref().unwrap().call_rpc_broadcast(
-        reliable,
-        method_id,
-        request_id,
-        arg_type,
-        msg_data,
-    );
-}
-#[no_mangle]
-pub unsafe extern "C" fn server_disconnect(_server: *mut Server, uuid: *const UuidFFI) {
-    let _client_uuid = uuid_from_ffi_ptr(uuid);
-
-    panic!("server disconnect not implemented")
-    // TODO uncomment when disconnect implemented
-    // server.as_ref().unwrap().disconnect();
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(msg_data)) = (
+            server.as_ref(),
+            slice_from_ffi_ptr(arg_data, arg_data_offset, arg_data_size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        let msg_data = match arg_data_size {
+            0 => None,
+            _ => Some(msg_data),
+        };
+        server
+            .call_rpc_broadcast(reliable, method_id, request_id, arg_type, msg_data)
+            .into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_disconnect(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid)) = (server.as_ref(), uuid_from_ffi_ptr(uuid)) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.kick(&client_uuid, "").into()
+    })
 }
 #[no_mangle]
-#[allow(unreachable_patterns)]
-pub unsafe extern "C" fn server_destroy(server: *mut Server) {
-    match server.as_mut() {
-        server_ref => {
-            drop(server_ref);
+pub unsafe extern "C" fn server_connection_info(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    out_status: *mut ConnectionRealTimeStatus,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(out_status)) =
+            (server.as_ref(), uuid_from_ffi_ptr(uuid), out_status.as_mut())
+        else {
+            return ServerFfiError::NullPointer;
+        };
+        let result = server.connection_info(&client_uuid);
+        if let Ok(status) = &result {
+            *out_status = *status;
         }
-        _ => (),
-    }
+        result.into()
+    })
+}
+/// IPv4 addresses are passed IPv6-mapped, the same as endpoints of callbacks
+#[no_mangle]
+pub unsafe extern "C" fn server_remote_addr(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    out_endpoint: *mut EndpointFFI,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(out_endpoint)) =
+            (server.as_ref(), uuid_from_ffi_ptr(uuid), out_endpoint.as_mut())
+        else {
+            return ServerFfiError::NullPointer;
+        };
+        match server.remote_addr(&client_uuid) {
+            Some(addr) => {
+                *out_endpoint = Endpoint {
+                    ip: addr.ip(),
+                    port: addr.port(),
+                }
+                .to_ffi();
+                ServerFfiError::None
+            }
+            None => ServerFfiError::UnknownPlayer,
+        }
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_traffic_stats(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    out_stats: *mut TrafficStats,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(out_stats)) =
+            (server.as_ref(), uuid_from_ffi_ptr(uuid), out_stats.as_mut())
+        else {
+            return ServerFfiError::NullPointer;
+        };
+        let result = server.traffic_stats(&client_uuid);
+        if let Ok(stats) = &result {
+            *out_stats = *stats;
+        }
+        result.into()
+    })
+}
+/// Returns `NotFound` if no message of the type was sent or received
+#[no_mangle]
+pub unsafe extern "C" fn server_message_type_stats(
+    server: *mut Server,
+    msg_type: i64,
+    out_stats: *mut TypeStats,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(out_stats)) = (server.as_ref(), out_stats.as_mut()) else {
+            return ServerFfiError::NullPointer;
+        };
+        match server.message_type_stats().get(&msg_type) {
+            Some(stats) => {
+                *out_stats = *stats;
+                ServerFfiError::None
+            }
+            None => ServerFfiError::NotFound,
+        }
+    })
+}
+/// Idle time in milliseconds
+#[no_mangle]
+pub unsafe extern "C" fn server_idle_duration(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    out_idle_ms: *mut u64,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(out_idle_ms)) =
+            (server.as_ref(), uuid_from_ffi_ptr(uuid), out_idle_ms.as_mut())
+        else {
+            return ServerFfiError::NullPointer;
+        };
+        match server.idle_duration(&client_uuid) {
+            Some(idle) => {
+                *out_idle_ms = idle.as_millis() as u64;
+                ServerFfiError::None
+            }
+            None => ServerFfiError::UnknownPlayer,
+        }
+    })
+}
+/// Messages over the limit are dropped, or the client is kicked if `kick` is true
+#[no_mangle]
+pub unsafe extern "C" fn server_set_rate_limit(
+    server: *mut Server,
+    max_messages_per_sec: u32,
+    max_bytes_per_sec: u64,
+    kick: bool,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        let policy = match kick {
+            true => RateLimitPolicy::Kick,
+            false => RateLimitPolicy::Drop,
+        };
+        let rate_limit = RateLimit::new(max_messages_per_sec, max_bytes_per_sec).with_policy(policy);
+        server.set_rate_limit(Some(rate_limit));
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_disable_rate_limit(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_rate_limit(None);
+        ServerFfiError::None
+    })
+}
+/// Null `uuid` sets the limit of all clients. `policy`: 0 - drop newest, 1 - drop oldest, 2 - kick
+#[no_mangle]
+pub unsafe extern "C" fn server_set_outgoing_limit(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    max_bytes: usize,
+    policy: u8,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        let policy = match policy {
+            0 => OverflowPolicy::DropNewest,
+            1 => OverflowPolicy::DropOldest,
+            2 => OverflowPolicy::Kick,
+            _ => return ServerFfiError::Failed,
+        };
+        let limit = OutgoingLimit::new(max_bytes).with_policy(policy);
+        server
+            .set_outgoing_limit(uuid_from_ffi_ptr(uuid).as_ref(), Some(limit))
+            .into()
+    })
+}
+/// Null `uuid` removes the limit of all clients, client's own limit is kept
+#[no_mangle]
+pub unsafe extern "C" fn server_disable_outgoing_limit(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .set_outgoing_limit(uuid_from_ffi_ptr(uuid).as_ref(), None)
+            .into()
+    })
+}
+/// `chunk_size == 0` sends to all clients at once
+#[no_mangle]
+pub unsafe extern "C" fn server_set_send_chunk_size(server: *mut Server, chunk_size: usize) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_send_chunk_size(match chunk_size {
+            0 => None,
+            chunk_size => Some(chunk_size),
+        });
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_set_accepting(server: *mut Server, accepting: bool) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_accepting(accepting);
+        ServerFfiError::None
+    })
+}
+/// `window_size == 0` disables deduplication
+#[no_mangle]
+pub unsafe extern "C" fn server_set_dedup(server: *mut Server, window_size: usize) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.set_dedup(window_size);
+        ServerFfiError::None
+    })
+}
+/// `poll_order`: 0 - events first, 1 - messages first
+#[no_mangle]
+pub unsafe extern "C" fn server_set_poll_order(server: *mut Server, poll_order: u8) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        let poll_order = match poll_order {
+            0 => PollOrder::EventsFirst,
+            1 => PollOrder::MessagesFirst,
+            _ => return ServerFfiError::Failed,
+        };
+        server.set_poll_order(poll_order);
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_configure_lanes(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    lanes: *const LaneConfig,
+    lane_count: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid)) = (server.as_ref(), uuid_from_ffi_ptr(uuid)) else {
+            return ServerFfiError::NullPointer;
+        };
+        let lanes = match (lanes.is_null(), lane_count) {
+            (_, 0) => &[],
+            (true, _) => return ServerFfiError::NullPointer,
+            (false, _) => core::slice::from_raw_parts(lanes, lane_count),
+        };
+        server.configure_lanes(&client_uuid, lanes).into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_send_on_lane(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    lane: u16,
+    reliable: bool,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(uuid),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        server
+            .send_on_lane(&client_uuid, lane, reliable, msg_type, msg_data)
+            .into()
+    })
+}
+/// Writes up to `buf_len` connected players into `out_buf` and the total number of connected players into `out_total`.
+/// Call again with a bigger buffer if the total exceeds `buf_len`
+#[no_mangle]
+pub unsafe extern "C" fn server_connected_players(
+    server: *mut Server,
+    out_buf: *mut UuidFFI,
+    buf_len: usize,
+    out_total: *mut usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(out_total)) = (server.as_ref(), out_total.as_mut()) else {
+            return ServerFfiError::NullPointer;
+        };
+        if out_buf.is_null() && buf_len > 0 {
+            return ServerFfiError::NullPointer;
+        }
+        let players = server.connected_players();
+        for (i, player) in players.iter().take(buf_len).enumerate() {
+            out_buf.add(i).write(player.to_ffi());
+        }
+        *out_total = players.len();
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_ban_ip(server: *mut Server, ip: *const c_char) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        match ip_from_ffi_ptr(ip) {
+            Ok(address) => {
+                server.ban_ip(address);
+                ServerFfiError::None
+            }
+            Err(error) => error,
+        }
+    })
+}
+/// `NotFound` if the address was not banned
+#[no_mangle]
+pub unsafe extern "C" fn server_unban_ip(server: *mut Server, ip: *const c_char) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        match ip_from_ffi_ptr(ip) {
+            Ok(address) if server.unban_ip(address) => ServerFfiError::None,
+            Ok(_) => ServerFfiError::NotFound,
+            Err(error) => error,
+        }
+    })
+}
+/// `reason` may be null
+#[no_mangle]
+pub unsafe extern "C" fn server_send_and_close(
+    server: *mut Server,
+    uuid: *const UuidFFI,
+    msg_type: i64,
+    data: *const c_uchar,
+    offset: isize,
+    size: usize,
+    reason: *const c_char,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid), Some(msg_data)) = (
+            server.as_ref(),
+            uuid_from_ffi_ptr(uuid),
+            slice_from_ffi_ptr(data, offset, size),
+        ) else {
+            return ServerFfiError::NullPointer;
+        };
+        let reason = str_from_ffi_ptr(reason).unwrap_or("");
+        server
+            .send_and_close(&client_uuid, msg_type, msg_data, reason)
+            .into()
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_flush_player(server: *mut Server, uuid: *const UuidFFI) -> ServerFfiError {
+    catch_panic(|| {
+        let (Some(server), Some(client_uuid)) = (server.as_ref(), uuid_from_ffi_ptr(uuid)) else {
+            return ServerFfiError::NullPointer;
+        };
+        server.flush_player(&client_uuid).into()
+    })
+}
+/// Returns `SendFailed` if data of any client could not be sent
+#[no_mangle]
+pub unsafe extern "C" fn server_flush_all(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        match server.flush_all().is_empty() {
+            true => ServerFfiError::None,
+            false => ServerFfiError::SendFailed,
+        }
+    })
+}
+/// `reason` may be null
+#[no_mangle]
+pub unsafe extern "C" fn server_shutdown(
+    server: *mut Server,
+    reason: *const c_char,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        server.shutdown(str_from_ffi_ptr(reason).unwrap_or(""));
+        ServerFfiError::None
+    })
+}
+/// Kicks every client for which `predicate` returns true.
+/// `out_count` (may be null) receives number of kicked clients, `reason` may be null
+#[no_mangle]
+pub unsafe extern "C" fn server_disconnect_where(
+    server: *mut Server,
+    predicate: ServerClientPredicate,
+    reason: *const c_char,
+    out_count: *mut usize,
+) -> ServerFfiError {
+    catch_panic(|| {
+        let Some(server) = server.as_ref() else {
+            return ServerFfiError::NullPointer;
+        };
+        let reason = str_from_ffi_ptr(reason).unwrap_or("");
+        let kicked = server.disconnect_where(|client| predicate(client.to_ffi()), reason);
+        if let Some(out_count) = out_count.as_mut() {
+            *out_count = kicked.len();
+        }
+        ServerFfiError::None
+    })
+}
+#[no_mangle]
+pub unsafe extern "C" fn server_destroy(server: *mut Server) -> ServerFfiError {
+    catch_panic(|| {
+        if server.is_null() {
+            return ServerFfiError::NullPointer;
+        }
+        drop(Box::from_raw(server));
+        ServerFfiError::None
+    })
 }
 
-unsafe fn uuid_from_ffi_ptr(uuid_ffi: *const UuidFFI) -> Uuid {
-    Uuid::from_bytes(uuid_ffi.as_ref().unwrap().bytes)
+unsafe fn uuid_from_ffi_ptr(uuid_ffi: *const UuidFFI) -> Option<Uuid> {
+    uuid_ffi
+        .as_ref()
+        .map(|uuid_ffi| Uuid::from_bytes(uuid_ffi.bytes))
+}
+unsafe fn uuids_from_ffi_ptr(uuids_ffi: *const UuidFFI, count: usize) -> Option<Vec<Uuid>> {
+    match (uuids_ffi.is_null(), count) {
+        (_, 0) => Some(Vec::new()),
+        (true, _) => None,
+        (false, _) => Some(
+            core::slice::from_raw_parts(uuids_ffi, count)
+                .iter()
+                .map(|uuid_ffi| Uuid::from_bytes(uuid_ffi.bytes))
+                .collect(),
+        ),
+    }
+}
+unsafe fn str_from_ffi_ptr<'s>(string: *const c_char) -> Result<&'s str, ServerFfiError> {
+    if string.is_null() {
+        return Err(ServerFfiError::NullPointer);
+    }
+    CStr::from_ptr(string)
+        .to_str()
+        .or(Err(ServerFfiError::InvalidEncoding))
+}
+unsafe fn ip_from_ffi_ptr(ip: *const c_char) -> Result<IpAddr, ServerFfiError> {
+    IpAddr::from_str(str_from_ffi_ptr(ip)?).or(Err(ServerFfiError::InvalidAddress))
 }
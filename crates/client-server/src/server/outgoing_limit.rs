@@ -0,0 +1,36 @@
+/// Limit of bytes waiting to be sent to a single client: queued in GNS (including sent but not yet acked
+/// reliable messages) and batched by the server. Checked for each client a regular message is sent to,
+/// see `Server::set_outgoing_limit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutgoingLimit {
+    pub max_bytes: usize,
+    pub policy: OverflowPolicy,
+}
+
+impl OutgoingLimit {
+    /// Limit dropping new messages over it
+    pub fn new(max_bytes: usize) -> OutgoingLimit {
+        OutgoingLimit {
+            max_bytes,
+            policy: OverflowPolicy::DropNewest,
+        }
+    }
+    pub fn with_policy(mut self, policy: OverflowPolicy) -> OutgoingLimit {
+        self.policy = policy;
+        self
+    }
+}
+
+/// What happens to a message which would exceed `OutgoingLimit`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The new message is dropped
+    #[default]
+    DropNewest,
+    /// Oldest messages batched for the client are dropped to make room for the new one.
+    /// GNS cannot cancel already queued messages, so without batching (see `Server::set_batching`)
+    /// it is the same as `DropNewest`. Dropping a part of a fragmented message drops the whole message on the client side
+    DropOldest,
+    /// Client is kicked and the message is dropped
+    Kick,
+}
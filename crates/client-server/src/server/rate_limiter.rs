@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// Application level limit of messages received from a single client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_messages_per_sec: u32,
+    pub max_bytes_per_sec: u64,
+    pub policy: RateLimitPolicy,
+}
+
+impl RateLimit {
+    /// Limit dropping messages over it
+    pub fn new(max_messages_per_sec: u32, max_bytes_per_sec: u64) -> RateLimit {
+        RateLimit {
+            max_messages_per_sec,
+            max_bytes_per_sec,
+            policy: RateLimitPolicy::Drop,
+        }
+    }
+    pub fn with_policy(mut self, policy: RateLimitPolicy) -> RateLimit {
+        self.policy = policy;
+        self
+    }
+}
+
+/// What happens to a client exceeding `RateLimit`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitPolicy {
+    /// Messages over the limit are dropped until the next second starts
+    #[default]
+    Drop,
+    /// Client is kicked on the first message over the limit
+    Kick,
+}
+
+#[derive(Debug)]
+struct RateWindow {
+    started_at: Instant,
+    messages: u32,
+    bytes: u64,
+}
+
+/// Counts received messages per client in fixed one second windows
+#[derive(Default, Debug)]
+pub struct RateLimiter {
+    windows: HashMap<Uuid, RateWindow>,
+}
+
+impl RateLimiter {
+    /// Accounts the message and returns false if it exceeds the limit
+    pub fn check(&mut self, client: &Uuid, bytes: usize, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let window = self.windows.entry(client.clone()).or_insert(RateWindow {
+            started_at: now,
+            messages: 0,
+            bytes: 0,
+        });
+        if now - window.started_at >= RATE_LIMIT_WINDOW {
+            window.started_at = now;
+            window.messages = 0;
+            window.bytes = 0;
+        }
+        window.messages += 1;
+        window.bytes += bytes as u64;
+        window.messages <= limit.max_messages_per_sec && window.bytes <= limit.max_bytes_per_sec
+    }
+    pub fn remove(&mut self, client: &Uuid) {
+        self.windows.remove(client);
+    }
+}
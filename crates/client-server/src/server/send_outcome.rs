@@ -0,0 +1,24 @@
+use gns_sys::EResult;
+
+/// Result of an unreliable send, see `Server::send`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendOutcome {
+    /// Message was queued by GNS (or batched, see `Server::set_batching`)
+    Queued,
+    /// Message was dropped because too much data is already queued for the connection.
+    /// Expected under buffer pressure, the sender should lower its send rate
+    Dropped,
+    /// GNS refused the message for another reason, e.g. the connection is being closed
+    Failed(EResult),
+}
+
+impl From<EResult> for SendOutcome {
+    fn from(result: EResult) -> Self {
+        match result {
+            EResult::k_EResultOK => SendOutcome::Queued,
+            // `k_EResultIgnored` is returned for dropped `NO_DELAY` messages
+            EResult::k_EResultLimitExceeded | EResult::k_EResultIgnored => SendOutcome::Dropped,
+            result => SendOutcome::Failed(result),
+        }
+    }
+}
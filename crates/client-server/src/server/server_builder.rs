@@ -0,0 +1,68 @@
+use std::{net::IpAddr, time::Duration};
+
+use gns::GnsConfig;
+use gns_sys::ESteamNetworkingConfigValue;
+
+use super::{server_error::ServerError, server_settings::ListenFamily, Server, ServerResult};
+
+/// Configures `Server` before its socket starts listening.
+/// GNS config values (e.g. timeouts) are set globally, so they are inherited by every connection
+/// accepted after `build`
+#[derive(Debug, Default, Clone)]
+pub struct ServerBuilder {
+    address: Option<(IpAddr, u16)>,
+    family: ListenFamily,
+    max_connections: Option<usize>,
+    max_connections_per_ip: Option<usize>,
+    connection_timeout: Option<Duration>,
+    auth_timeout: Option<Duration>,
+}
+
+impl ServerBuilder {
+    pub fn new() -> ServerBuilder {
+        Default::default()
+    }
+    pub fn bind(mut self, ip: IpAddr, port: u16) -> ServerBuilder {
+        self.address = Some((ip, port));
+        self
+    }
+    pub fn listen_family(mut self, family: ListenFamily) -> ServerBuilder {
+        self.family = family;
+        self
+    }
+    pub fn max_connections(mut self, max_connections: usize) -> ServerBuilder {
+        self.max_connections = Some(max_connections);
+        self
+    }
+    pub fn max_connections_per_ip(mut self, max_connections: usize) -> ServerBuilder {
+        self.max_connections_per_ip = Some(max_connections);
+        self
+    }
+    /// Time without any reply after which an established connection is considered dead
+    pub fn connection_timeout(mut self, timeout: Duration) -> ServerBuilder {
+        self.connection_timeout = Some(timeout);
+        self
+    }
+    /// Time a connected client has to pass authentication before it is closed
+    pub fn auth_timeout(mut self, timeout: Duration) -> ServerBuilder {
+        self.auth_timeout = Some(timeout);
+        self
+    }
+    pub fn build<'a>(self) -> ServerResult<Server<'a>> {
+        let (ip, port) = self.address.ok_or(ServerError::Listen)?;
+        if let Some(timeout) = self.connection_timeout {
+            Server::apply_connection_config(
+                ESteamNetworkingConfigValue::k_ESteamNetworkingConfig_TimeoutConnected,
+                GnsConfig::Int32(timeout.as_millis() as _),
+            )?;
+        }
+        let server = Server::new_with_family(ip, port, self.family)?;
+        server.settings.borrow_mut().connection_timeout = self.connection_timeout;
+        server.set_max_connections(self.max_connections);
+        server.set_max_connections_per_ip(self.max_connections_per_ip);
+        if let Some(timeout) = self.auth_timeout {
+            server.set_auth_timeout(timeout);
+        }
+        Ok(server)
+    }
+}
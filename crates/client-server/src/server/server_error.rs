@@ -0,0 +1,65 @@
+use std::{fmt::Display, net::IpAddr};
+
+use gns_sys::EResult;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerError {
+    GnsInit(String),
+    SocketCreate,
+    Listen,
+    Accept,
+    UnknownPlayer,
+    UnknownConnection,
+    MessageEncode,
+    SendFailed(EResult),
+    MessageTooLarge { size: usize, max: usize },
+    ReservedMessageType(i64),
+    RpcMethodRegistered(i64),
+    ConnectionStatus,
+    ConnectionConfig,
+    LaneConfig(EResult),
+    UnsupportedListenAddress(IpAddr),
+    Process(Vec<ServerError>),
+}
+
+impl Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::GnsInit(reason) => write!(f, "GNS initialization failed: {}", reason),
+            ServerError::SocketCreate => write!(f, "Cannot create socket"),
+            ServerError::Listen => write!(f, "Cannot create server socket"),
+            ServerError::Accept => write!(f, "Cannot accept the connection"),
+            ServerError::UnknownPlayer => write!(f, "There is not such client"),
+            ServerError::UnknownConnection => write!(f, "Unknown connection"),
+            ServerError::MessageEncode => write!(f, "Cannot encode message"),
+            ServerError::SendFailed(result) => write!(f, "Send failed: {:?}", result),
+            ServerError::MessageTooLarge { size, max } => {
+                write!(f, "Message is too large: {} bytes, max is {} bytes", size, max)
+            }
+            ServerError::ReservedMessageType(msg_type) => {
+                write!(f, "Message type {} is reserved, application types must be non-negative", msg_type)
+            }
+            ServerError::RpcMethodRegistered(method_id) => {
+                write!(f, "Rpc method {} is already registered", method_id)
+            }
+            ServerError::ConnectionStatus => write!(f, "Cannot get connection status"),
+            ServerError::ConnectionConfig => write!(f, "Cannot set connection config value"),
+            ServerError::LaneConfig(result) => write!(f, "Cannot configure connection lanes: {:?}", result),
+            ServerError::UnsupportedListenAddress(ip) => {
+                write!(f, "Cannot listen on {}, multicast, broadcast and link-local IPv6 addresses are not supported", ip)
+            }
+            ServerError::Process(errors) => {
+                write!(f, "{} errors occurred during processing: ", errors.len())?;
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
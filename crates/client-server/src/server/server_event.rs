@@ -0,0 +1,33 @@
+use omgpp_core::{DisconnectReason, Endpoint};
+use uuid::Uuid;
+
+/// Event collected by `Server::poll_into`/`Server::poll_events` instead of invoking a callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerEvent {
+    pub sender: Uuid,
+    pub endpoint: Endpoint,
+    pub kind: ServerEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerEventKind {
+    /// Client passed authentication
+    Connected,
+    /// Client resumed its previous session and passed authentication
+    Reconnected,
+    Disconnected(DisconnectReason),
+    Message {
+        reliable: bool,
+        msg_type: i64,
+        data: Vec<u8>,
+    },
+    Rpc {
+        reliable: bool,
+        method_id: i64,
+        request_id: u64,
+        arg_type: i64,
+        arg_data: Vec<u8>,
+    },
+    /// Connection crossed `QualityThresholds`, use `Server::connection_info` for details
+    ConnectionQualityChanged { poor: bool },
+}
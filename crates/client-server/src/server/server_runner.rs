@@ -0,0 +1,107 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::{
+    callback_pool::ServerTask, server_builder::ServerBuilder, server_error::ServerError,
+    server_event::ServerEvent, Server, ServerResult,
+};
+
+/// Server driven by a background thread, see `ServerBuilder::spawn`
+pub struct ServerHandle {
+    events: Receiver<ServerEvent>,
+    tasks: Sender<ServerTask>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// Events collected by the background thread, see `Server::poll_events`
+    pub fn events(&self) -> &Receiver<ServerEvent> {
+        &self.events
+    }
+    /// Runs `task` on the server thread before the next tick, e.g. to send a message
+    pub fn execute(&self, task: impl FnOnce(&Server) + Send + 'static) {
+        _ = self.tasks.send(Box::new(task));
+    }
+    /// Closes every connection and waits for the server thread to finish
+    pub fn shutdown(mut self) {
+        self.stop_and_join();
+    }
+    fn stop_and_join(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+impl ServerBuilder {
+    /// Creates the server on a background thread which calls `process` `tick_hz` times per second
+    /// and delivers events through `ServerHandle::events`.
+    /// `Server` is not `Send`, so callbacks can't be registered; the server is accessible via `ServerHandle::execute`
+    pub fn spawn(self, tick_hz: u32) -> ServerResult<ServerHandle> {
+        let (events_tx, events_rx) = mpsc::channel();
+        let (tasks_tx, tasks_rx) = mpsc::channel::<ServerTask>();
+        let (started_tx, started_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let tick = Duration::from_secs_f64(1.0 / tick_hz.max(1) as f64);
+
+        let thread = thread::spawn(move || {
+            let server = match self.build() {
+                Ok(server) => {
+                    _ = started_tx.send(Ok(()));
+                    server
+                }
+                Err(err) => {
+                    _ = started_tx.send(Err(err));
+                    return;
+                }
+            };
+            while !thread_stop.load(Ordering::Relaxed) {
+                let tick_started = Instant::now();
+                while let Ok(task) = tasks_rx.try_recv() {
+                    task(&server);
+                }
+                for event in server.poll_events::<128>() {
+                    // nobody listens, keep processing anyway
+                    _ = events_tx.send(event);
+                }
+                if let Some(left) = tick.checked_sub(tick_started.elapsed()) {
+                    thread::sleep(left);
+                }
+            }
+            server.shutdown("Server stopped");
+        });
+
+        match started_rx.recv() {
+            Ok(Ok(())) => Ok(ServerHandle {
+                events: events_rx,
+                tasks: tasks_tx,
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => {
+                _ = thread.join();
+                Err(err)
+            }
+            Err(_) => {
+                _ = thread.join();
+                Err(ServerError::Listen)
+            }
+        }
+    }
+}
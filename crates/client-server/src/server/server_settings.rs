@@ -1,4 +1,69 @@
+use std::{collections::HashSet, net::IpAddr, time::Duration};
+
+use omgpp_core::{compression::Compression, ConnectionRealTimeStatus};
+
+use super::outgoing_limit::OutgoingLimit;
+use super::rate_limiter::RateLimit;
+
 #[derive(Default)]
 pub struct ServerSettings{
-    pub resource_location : String      //url
-}
\ No newline at end of file
+    pub resource_location : String,     //url
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+    pub banned_ips: HashSet<IpAddr>,    // ipv4 addresses are stored as ipv6 mapped
+    pub refuse_connections: bool,
+    pub fragmentation: bool,
+    pub batching: bool,
+    pub send_chunk_size: Option<usize>,
+    pub poll_order: PollOrder,
+    pub compression: Compression,
+    pub rate_limit: Option<RateLimit>,
+    pub outgoing_limit: Option<OutgoingLimit>,
+    pub dedup_window: Option<usize>,
+    pub quality_thresholds: Option<QualityThresholds>,
+    pub connection_timeout: Option<Duration>,
+    pub ack_resend_timeout: Option<Duration>,
+}
+/// Connection is considered poor while its ping or packet loss exceeds the threshold.
+/// Real-time status of every connection is sampled once per `sample_interval`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityThresholds {
+    pub max_ping_ms: i32,
+    /// Fraction of packets lost, `0.0..=1.0`
+    pub max_packet_loss: f32,
+    pub sample_interval: Duration,
+}
+impl QualityThresholds {
+    pub fn is_poor(&self, status: &ConnectionRealTimeStatus) -> bool {
+        // GNS reports negative quality while there is not enough data
+        let packet_loss = match status.connection_quality_local {
+            quality if quality < 0.0 => 0.0,
+            quality => 1.0 - quality,
+        };
+        status.ping > self.max_ping_ms || packet_loss > self.max_packet_loss
+    }
+}
+/// Order of the two polling phases of `Server::process`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollOrder {
+    /// Connection changes are handled before messages received within the same cycle
+    #[default]
+    EventsFirst,
+    /// Messages are handled before connection changes, e.g. so state updates of a client
+    /// are applied before other clients join
+    MessagesFirst,
+}
+/// Address family of the listen socket.
+/// GNS takes IPv6 addresses only: IPv4 addresses are passed IPv6-mapped and GNS binds an IPv4-only (`AF_INET`) socket for them,
+/// other addresses are bound as a dual stack IPv6 socket
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenFamily {
+    /// IPv4 address is bound as IPv4-only socket, IPv6 address as a dual stack one
+    #[default]
+    Auto,
+    /// IPv4-only socket. Requires IPv4 (or IPv4-mapped) address
+    Ipv4,
+    /// Dual stack socket accepting both IPv4 and IPv6 clients; `0.0.0.0` is bound as `::`.
+    /// Dual stack sockets may fail to be created on Windows when IPv6 is disabled, use `Ipv4` there
+    DualStack,
+}
@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+// long enough to reconnect after the connection timed out
+const DEFAULT_SESSION_EXPIRE_PERIOD: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Session {
+    token: String,
+    // None while the client is connected
+    disconnected_at: Option<Instant>,
+}
+
+/// Session tokens issued to authenticated clients. A client presenting the token of its previous
+/// session before auth resumes the previous uuid instead of getting a new one
+#[derive(Debug)]
+pub struct SessionTracker {
+    sessions: HashMap<Uuid, Session>,
+    // clients which resumed their session and are not authenticated yet
+    resumed: HashSet<Uuid>,
+    expire_period: Duration,
+}
+
+impl Default for SessionTracker {
+    fn default() -> Self {
+        SessionTracker {
+            sessions: Default::default(),
+            resumed: Default::default(),
+            expire_period: DEFAULT_SESSION_EXPIRE_PERIOD,
+        }
+    }
+}
+
+impl SessionTracker {
+    /// Time a disconnected client has to resume its session
+    pub fn set_expire_period(&mut self, period: Duration) {
+        self.expire_period = period;
+    }
+    pub fn expire_period(&self) -> Duration {
+        self.expire_period
+    }
+    /// Returns uuid of the session the token belongs to, if it has not expired
+    pub fn client_by_token(&self, token: &str) -> Option<Uuid> {
+        self.sessions
+            .iter()
+            .find(|(_client, session)| session.token == token && !self.is_expired(session))
+            .map(|(client, _session)| client.clone())
+    }
+    /// Marks the client as resumed, so `open` continues its session
+    pub fn track_client_resumed(&mut self, client: &Uuid) {
+        self.resumed.insert(client.clone());
+    }
+    /// Starts (or continues a resumed) session of the authenticated client.
+    /// Returns its token and whether the session was resumed
+    pub fn open(&mut self, client: &Uuid) -> (String, bool) {
+        let expire_period = self.expire_period;
+        // expired sessions are dropped here, so the map doesn't grow with every client ever connected
+        self.sessions.retain(|_client, session| {
+            session
+                .disconnected_at
+                .map_or(true, |at| at.elapsed() <= expire_period)
+        });
+        let resumed = self.resumed.remove(client);
+        let session = self.sessions.entry(client.clone()).or_insert_with(|| Session {
+            token: Uuid::new_v4().simple().to_string(),
+            disconnected_at: None,
+        });
+        session.disconnected_at = None;
+        (session.token.clone(), resumed)
+    }
+    pub fn track_client_disconnected(&mut self, client: &Uuid) {
+        self.resumed.remove(client);
+        if let Some(session) = self.sessions.get_mut(client) {
+            session.disconnected_at = Some(Instant::now());
+        }
+    }
+    fn is_expired(&self, session: &Session) -> bool {
+        session
+            .disconnected_at
+            .is_some_and(|at| at.elapsed() > self.expire_period)
+    }
+    /// Forgets the session, so it can't be resumed
+    pub fn remove(&mut self, client: &Uuid) {
+        self.resumed.remove(client);
+        self.sessions.remove(client);
+    }
+}
@@ -0,0 +1,65 @@
+//! Connection events of tracked clients made up without GNS, so connection callbacks can be tested in isolation
+//! (see `Server::inject_connection_event`). Connect requests need a real client, see `Loopback`.
+use gns_sys::ESteamNetworkingConnectionState;
+use uuid::Uuid;
+
+/// State transition of the client's connection as GNS reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntheticConnectionEvent {
+    pub client: Uuid,
+    pub old_state: ESteamNetworkingConnectionState,
+    pub new_state: ESteamNetworkingConnectionState,
+    /// One of `ESteamNetConnectionEnd` codes, `0` while the connection is alive
+    pub end_reason: i32,
+    pub end_debug: String,
+}
+
+impl SyntheticConnectionEvent {
+    pub fn new(
+        client: Uuid,
+        old_state: ESteamNetworkingConnectionState,
+        new_state: ESteamNetworkingConnectionState,
+    ) -> SyntheticConnectionEvent {
+        SyntheticConnectionEvent {
+            client,
+            old_state,
+            new_state,
+            end_reason: 0,
+            end_debug: String::new(),
+        }
+    }
+    /// Accepted connection is established: `Connecting` -> `Connected`
+    pub fn connected(client: Uuid) -> SyntheticConnectionEvent {
+        SyntheticConnectionEvent::new(
+            client,
+            ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connecting,
+            ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
+        )
+    }
+    /// Client closed the connection: `Connected` -> `ClosedByPeer`
+    pub fn closed_by_peer(client: Uuid, end_reason: i32) -> SyntheticConnectionEvent {
+        SyntheticConnectionEvent::new(
+            client,
+            ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
+            ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ClosedByPeer,
+        )
+        .with_end_reason(end_reason)
+    }
+    /// Connection was lost: `Connected` -> `ProblemDetectedLocally`, e.g. with `5002` (timeout)
+    pub fn problem_detected(client: Uuid, end_reason: i32) -> SyntheticConnectionEvent {
+        SyntheticConnectionEvent::new(
+            client,
+            ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_Connected,
+            ESteamNetworkingConnectionState::k_ESteamNetworkingConnectionState_ProblemDetectedLocally,
+        )
+        .with_end_reason(end_reason)
+    }
+    pub fn with_end_reason(mut self, end_reason: i32) -> SyntheticConnectionEvent {
+        self.end_reason = end_reason;
+        self
+    }
+    pub fn with_end_debug(mut self, end_debug: &str) -> SyntheticConnectionEvent {
+        self.end_debug = end_debug.to_string();
+        self
+    }
+}
@@ -0,0 +1,209 @@
+//! Connection lifecycle of a server and a client driven by `Loopback`.
+//! Run with `cargo test -p client-server --features test-util`
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+use client_server::{
+    loopback::Loopback,
+    server::{outgoing_limit::OutgoingLimit, synthetic_event::SyntheticConnectionEvent},
+};
+use gns_sys::EResult;
+use omgpp_core::ConnectionState;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn loopback() -> Loopback<'static> {
+    Loopback::new(0).expect("server must listen on a free port")
+}
+
+// states the server reported for its clients, in order
+fn record_server_states(loopback: &Loopback) -> Rc<RefCell<Vec<ConnectionState>>> {
+    let states = Rc::new(RefCell::new(Vec::new()));
+    let recorded = states.clone();
+    loopback
+        .server
+        .register_on_connection_state_changed(move |_server, _client, _endpoint, state, _reason| {
+            recorded.borrow_mut().push(state);
+        });
+    states
+}
+
+// states the client reported for its connection to the server, in order
+fn record_client_states(loopback: &Loopback) -> Rc<RefCell<Vec<ConnectionState>>> {
+    let states = Rc::new(RefCell::new(Vec::new()));
+    let recorded = states.clone();
+    loopback
+        .client
+        .register_on_connection_state_changed(move |_client, _endpoint, state| {
+            recorded.borrow_mut().push(state);
+        });
+    states
+}
+
+#[test]
+fn binds_free_port() {
+    let loopback = loopback();
+    assert_ne!(loopback.server.local_addr().port(), 0);
+    assert!(loopback.server.local_addr().ip().is_loopback());
+}
+
+#[test]
+fn connects_through_authentication() {
+    let mut loopback = loopback();
+    let server_states = record_server_states(&loopback);
+
+    loopback.connect(TIMEOUT).expect("client must connect");
+
+    assert!(loopback.pump_until(|loopback| loopback.server.connected_players().len() == 1, TIMEOUT));
+    assert_eq!(
+        *server_states.borrow(),
+        vec![
+            ConnectionState::Connecting,
+            ConnectionState::ConnectedUnverified,
+            ConnectionState::Connected,
+        ]
+    );
+    assert_eq!(loopback.client.state(), ConnectionState::Connected);
+}
+
+#[test]
+fn client_disconnect_is_reported_to_server() {
+    let mut loopback = loopback();
+    loopback.connect(TIMEOUT).expect("client must connect");
+    let server_states = record_server_states(&loopback);
+    let client = loopback.server.connected_players()[0];
+
+    loopback.client.disconnect();
+
+    assert!(loopback.pump_until(|loopback| !server_states.borrow().is_empty(), TIMEOUT));
+    assert_eq!(*server_states.borrow(), vec![ConnectionState::Disconnected]);
+    assert!(loopback.server.connected_players().is_empty());
+    assert!(!loopback.server.is_connected(&client));
+}
+
+#[test]
+fn kicked_client_is_disconnected() {
+    let mut loopback = loopback();
+    loopback.connect(TIMEOUT).expect("client must connect");
+    let server_states = record_server_states(&loopback);
+    let client_states = record_client_states(&loopback);
+    let client = loopback.server.connected_players()[0];
+
+    loopback.server.kick(&client, "kicked by test").expect("client must be kicked");
+
+    // the server reports the kick right away, the client once the close reaches it
+    assert_eq!(*server_states.borrow(), vec![ConnectionState::Disconnected]);
+    assert!(loopback.pump_until(|loopback| loopback.client.state() == ConnectionState::Disconnected, TIMEOUT));
+    assert_eq!(*client_states.borrow(), vec![ConnectionState::Disconnected]);
+    assert!(loopback.server.connected_players().is_empty());
+}
+
+#[test]
+fn reliable_messages_arrive_in_send_order() {
+    const MESSAGE_COUNT: u32 = 100;
+    let mut loopback = loopback();
+    loopback.connect(TIMEOUT).expect("client must connect");
+    let received = Rc::new(RefCell::new(Vec::new()));
+    let recorded = received.clone();
+    loopback
+        .client
+        .register_on_message(move |_client, _endpoint, _reliable, _msg_type, data| {
+            recorded.borrow_mut().push(u32::from_le_bytes(data.try_into().unwrap()));
+        });
+    let client = loopback.server.connected_players()[0];
+
+    for index in 0..MESSAGE_COUNT {
+        loopback
+            .server
+            .send_reliable_ordered(&client, 1, &index.to_le_bytes())
+            .expect("message must be queued");
+    }
+
+    assert!(loopback.pump_until(|_loopback| received.borrow().len() == MESSAGE_COUNT as usize, TIMEOUT));
+    assert_eq!(*received.borrow(), (0..MESSAGE_COUNT).collect::<Vec<_>>());
+}
+
+#[test]
+fn synthetic_timeout_is_reported_as_timed_out() {
+    const END_REASON_MISC_TIMEOUT: i32 = 5002;
+    let mut loopback = loopback();
+    loopback.connect(TIMEOUT).expect("client must connect");
+    let server_states = record_server_states(&loopback);
+    let client = loopback.server.connected_players()[0];
+
+    loopback
+        .server
+        .inject_connection_event(&SyntheticConnectionEvent::problem_detected(client, END_REASON_MISC_TIMEOUT))
+        .expect("client must be tracked");
+
+    assert_eq!(*server_states.borrow(), vec![ConnectionState::TimedOut]);
+    assert!(loopback.server.connected_players().is_empty());
+}
+
+#[test]
+fn tick_is_invoked_once_per_call() {
+    let loopback = loopback();
+    let ticks = Rc::new(Cell::new(0));
+    let counted = ticks.clone();
+    loopback.server.register_on_tick(move |_server| counted.set(counted.get() + 1));
+
+    // an idle server polls many times before the timeout
+    loopback
+        .server
+        .process_blocking::<16>(Duration::from_millis(20))
+        .expect("idle server must not fail");
+    assert_eq!(ticks.get(), 1);
+
+    _ = loopback.server.drain_events().count();
+    assert_eq!(ticks.get(), 2);
+
+    loopback.server.process::<16>().expect("idle server must not fail");
+    assert_eq!(ticks.get(), 3);
+}
+
+#[test]
+fn flush_is_not_reported_to_client() {
+    let mut loopback = loopback();
+    loopback.connect(TIMEOUT).expect("client must connect");
+    let received = Rc::new(RefCell::new(Vec::new()));
+    let recorded = received.clone();
+    loopback
+        .client
+        .register_on_message(move |_client, _endpoint, _reliable, msg_type, _data| {
+            recorded.borrow_mut().push(msg_type);
+        });
+    let client = loopback.server.connected_players()[0];
+
+    loopback.server.flush_player(&client).expect("client must be flushed");
+    assert!(loopback.server.flush_all().is_empty());
+    loopback.server.send_reliable(&client, 7, b"after flush").expect("message must be queued");
+
+    assert!(loopback.pump_until(|_loopback| !received.borrow().is_empty(), TIMEOUT));
+    // unreliable flush messages may arrive after the reliable one, give them time to reach the client
+    loopback.pump_until(|_loopback| false, Duration::from_millis(100));
+    assert_eq!(*received.borrow(), vec![7]);
+}
+
+#[test]
+fn broadcast_respects_outgoing_limit() {
+    let mut loopback = loopback();
+    loopback.connect(TIMEOUT).expect("client must connect");
+    let client = loopback.server.connected_players()[0];
+
+    // nothing fits into an empty limit
+    loopback
+        .server
+        .set_outgoing_limit(None, Some(OutgoingLimit::new(0)))
+        .expect("default limit must be set");
+
+    let failed = loopback.server.broadcast_reliable(1, b"over limit").expect("broadcast must be encoded");
+    assert_eq!(failed, vec![(client, EResult::k_EResultLimitExceeded)]);
+    let failed = loopback
+        .server
+        .send_to_many(&[client], 1, b"over limit")
+        .expect("message must be encoded");
+    assert_eq!(failed, vec![(client, EResult::k_EResultLimitExceeded)]);
+}
@@ -21,7 +21,9 @@ fn main() {
     csbindgen::Builder::default()
         .input_extern_file("src/ffi.rs")
         .input_extern_file("src/lib.rs")
-        .always_included_types(["EndpointFFI", "UuidFFI","ConnectionState"])
+        .always_included_types(["EndpointFFI", "UuidFFI","ConnectionState", "ConnectionRealTimeStatus", "ProcessStats", "TrafficStats", "TypeStats", "LaneConfig"])
+        // exported FFI functions are linked into the client-server library
+        .csharp_dll_name("client_server")
         .csharp_class_name("OmgppCoreNative")
         .csharp_class_accessibility("public")
         .csharp_namespace("OmgppNative")
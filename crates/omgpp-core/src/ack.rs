@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Number of sequences before the latest one an ack reports, one bit each
+pub const ACK_BITS: u64 = 32;
+
+/// Receiving side of unreliable messages sent with an ack request.
+/// Remembers the latest received sequence and which of the `ACK_BITS` sequences before it were received,
+/// which is sent back to the sender as a compact ack (see `OmgppPredefinedCmd::ACK`)
+#[derive(Debug, Default)]
+pub struct AckReceiver {
+    latest: u64,
+    // bit `i` is set if `latest - 1 - i` was received
+    bits: u32,
+    // something was received since the last `take_ack`
+    dirty: bool,
+}
+
+impl AckReceiver {
+    /// Remembers the sequence. Returns false if the message must be dropped: it was already received
+    /// (retransmitted because the ack was lost) or it is older than the ack window
+    pub fn receive(&mut self, sequence: u64) -> bool {
+        // duplicates are acked again, the previous ack may have been lost
+        self.dirty = true;
+        if sequence > self.latest {
+            let shift = sequence - self.latest;
+            self.bits = match (self.latest, shift) {
+                (0, _) => 0,
+                (_, shift) if shift > ACK_BITS => 0,
+                (_, shift) => (((self.bits as u64) << shift) | (1 << (shift - 1))) as u32,
+            };
+            self.latest = sequence;
+            return true;
+        }
+        let distance = self.latest - sequence;
+        if distance == 0 || distance > ACK_BITS {
+            return false;
+        }
+        let bit = 1 << (distance - 1);
+        let is_new = self.bits & bit == 0;
+        self.bits |= bit;
+        is_new
+    }
+    /// Latest sequence and bits of the ones before it, if anything was received since the last call
+    pub fn take_ack(&mut self) -> Option<(u64, u32)> {
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        Some((self.latest, self.bits))
+    }
+}
+
+#[derive(Debug)]
+struct PendingMessage {
+    bytes: Vec<u8>,
+    sent_at: Instant,
+    retransmits: u32,
+}
+
+/// Sending side of unreliable messages sent with an ack request.
+/// Keeps encoded messages until they are acknowledged, so the lost ones can be sent again as they are
+#[derive(Debug, Default)]
+pub struct AckSender {
+    last_sequence: u64,
+    pending: BTreeMap<u64, PendingMessage>,
+}
+
+impl AckSender {
+    /// Sequences start with `1`, `0` marks messages which are not numbered
+    pub fn next_sequence(&mut self) -> u64 {
+        self.last_sequence += 1;
+        self.last_sequence
+    }
+    pub fn track_sent(&mut self, sequence: u64, bytes: Vec<u8>) {
+        self.pending.insert(
+            sequence,
+            PendingMessage {
+                bytes,
+                sent_at: Instant::now(),
+                retransmits: 0,
+            },
+        );
+    }
+    /// Forgets messages reported by the ack. Returns how many of them were still pending
+    pub fn acknowledge(&mut self, latest: u64, bits: u32) -> usize {
+        let acked = std::iter::once(latest).chain(
+            (0..ACK_BITS)
+                .filter(|i| bits & (1 << i) != 0)
+                .filter_map(|i| latest.checked_sub(i + 1)),
+        );
+        acked
+            .filter(|sequence| self.pending.remove(sequence).is_some())
+            .count()
+    }
+    /// Messages not acknowledged within `timeout` since they were (re)sent, oldest first.
+    /// Messages already retransmitted `max_retransmits` times are given up on and dropped
+    pub fn due_retransmits(&mut self, timeout: Duration, max_retransmits: u32) -> Vec<Vec<u8>> {
+        self.pending.retain(|_sequence, message| {
+            message.retransmits < max_retransmits || message.sent_at.elapsed() < timeout
+        });
+        self.pending
+            .values_mut()
+            .filter(|message| message.sent_at.elapsed() >= timeout)
+            .map(|message| {
+                message.sent_at = Instant::now();
+                message.retransmits += 1;
+                message.bytes.clone()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_beyond_window_clears_bits() {
+        let mut receiver = AckReceiver::default();
+        assert!(receiver.receive(1));
+        assert!(receiver.receive(2));
+        assert_eq!(receiver.take_ack(), Some((2, 0b1)));
+
+        assert!(receiver.receive(2 + ACK_BITS + 1));
+        assert_eq!(receiver.take_ack(), Some((2 + ACK_BITS + 1, 0)));
+    }
+
+    #[test]
+    fn jump_to_window_edge_keeps_only_previous_latest() {
+        let mut receiver = AckReceiver::default();
+        assert!(receiver.receive(1));
+        assert!(receiver.receive(2));
+        // the bit of `1` is shifted out of the `u32`, the one of `2` lands on the last bit
+        assert!(receiver.receive(2 + ACK_BITS));
+        assert_eq!(
+            receiver.take_ack(),
+            Some((2 + ACK_BITS, 1 << (ACK_BITS - 1)))
+        );
+    }
+
+    #[test]
+    fn duplicate_is_dropped_but_acked_again() {
+        let mut receiver = AckReceiver::default();
+        assert!(receiver.receive(7));
+        assert_eq!(receiver.take_ack(), Some((7, 0)));
+        assert_eq!(receiver.take_ack(), None);
+
+        assert!(!receiver.receive(7));
+        assert_eq!(receiver.take_ack(), Some((7, 0)));
+    }
+
+    #[test]
+    fn message_older_than_window_is_dropped() {
+        let mut receiver = AckReceiver::default();
+        assert!(receiver.receive(ACK_BITS + 2));
+        assert!(!receiver.receive(1));
+        assert_eq!(receiver.take_ack(), Some((ACK_BITS + 2, 0)));
+    }
+
+    #[test]
+    fn out_of_order_message_inside_window_is_accepted_once() {
+        let mut receiver = AckReceiver::default();
+        assert!(receiver.receive(1));
+        assert!(receiver.receive(5));
+        assert!(receiver.receive(3));
+        assert!(!receiver.receive(3));
+        // bits of `4` and `2`, never received, stay clear
+        assert_eq!(receiver.take_ack(), Some((5, 0b1010)));
+    }
+
+    #[test]
+    fn acknowledge_forgets_only_reported_sequences() {
+        let mut sender = AckSender::default();
+        for _ in 0..5 {
+            let sequence = sender.next_sequence();
+            sender.track_sent(sequence, vec![sequence as u8]);
+        }
+        // 5, 4 (bit 0) and 2 (bit 2)
+        assert_eq!(sender.acknowledge(5, 0b101), 3);
+        assert_eq!(sender.acknowledge(5, 0b101), 0);
+        // 3 and 1 are still pending
+        assert_eq!(sender.acknowledge(3, 0b11), 2);
+    }
+
+    #[test]
+    fn acknowledge_at_window_edge_does_not_underflow() {
+        let mut sender = AckSender::default();
+        let sequence = sender.next_sequence();
+        sender.track_sent(sequence, vec![1]);
+        assert_eq!(sender.acknowledge(sequence, u32::MAX), 1);
+    }
+
+    #[test]
+    fn retransmits_are_given_up_after_limit() {
+        const MAX_RETRANSMITS: u32 = 2;
+        let mut sender = AckSender::default();
+        let sequence = sender.next_sequence();
+        sender.track_sent(sequence, vec![1, 2, 3]);
+
+        assert!(sender
+            .due_retransmits(Duration::from_secs(60), MAX_RETRANSMITS)
+            .is_empty());
+        for _ in 0..MAX_RETRANSMITS {
+            assert_eq!(
+                sender.due_retransmits(Duration::ZERO, MAX_RETRANSMITS),
+                vec![vec![1, 2, 3]]
+            );
+        }
+        assert!(sender
+            .due_retransmits(Duration::ZERO, MAX_RETRANSMITS)
+            .is_empty());
+        // dropped, not just skipped
+        assert_eq!(sender.acknowledge(sequence, 0), 0);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{fragmentation::MAX_FRAGMENTED_SIZE, MAX_MESSAGE_SIZE};
+
+/// Values of the `compression` field of the regular message
+pub const COMPRESSION_NONE: u32 = 0;
+pub const COMPRESSION_ZSTD: u32 = 1;
+pub const COMPRESSION_LZ4: u32 = 2;
+
+/// Payloads smaller than that are sent as is
+pub const COMPRESSION_THRESHOLD: usize = 256;
+/// Single (not fragmented) messages decompressed above the limit are rejected, so a small message can't make
+/// the receiver allocate more than an uncompressed message would. Senders fragment (or reject) larger data
+/// even if it compresses below `MAX_MESSAGE_SIZE`
+pub const MAX_DECOMPRESSED_MESSAGE_SIZE: usize = MAX_MESSAGE_SIZE;
+/// Same for reassembled fragmented messages
+pub const MAX_DECOMPRESSED_SIZE: usize = MAX_FRAGMENTED_SIZE;
+
+/// Compression applied to outgoing regular messages. The receiver decompresses any supported algorithm
+/// regardless of its own setting, so compressed and not compressed traffic can be mixed
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    #[cfg(feature = "zstd")]
+    Zstd { level: i32 },
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Compression {
+    /// Returns the `compression` field value and compressed data.
+    /// Data is left as is if it's below `COMPRESSION_THRESHOLD` or compression doesn't make it smaller
+    pub fn compress(&self, data: &[u8]) -> (u32, Vec<u8>) {
+        if data.len() < COMPRESSION_THRESHOLD {
+            return (COMPRESSION_NONE, Vec::from(data));
+        }
+        let compressed: Option<(u32, Vec<u8>)> = match self {
+            Compression::None => None,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level } => zstd::bulk::compress(data, *level)
+                .ok()
+                .map(|compressed| (COMPRESSION_ZSTD, compressed)),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Some((COMPRESSION_LZ4, lz4_flex::compress_prepend_size(data))),
+        };
+        match compressed {
+            Some((compression, compressed)) if compressed.len() < data.len() => (compression, compressed),
+            _ => (COMPRESSION_NONE, Vec::from(data)),
+        }
+    }
+}
+
+/// Decompresses `data` of a received regular message. The output is allocated with the size the sender declared
+/// (zstd frame header, lz4 size prefix), which must not exceed `max_size`: `MAX_DECOMPRESSED_MESSAGE_SIZE`
+/// for single messages and `MAX_DECOMPRESSED_SIZE` for reassembled fragmented ones
+pub fn decompress(compression: u32, data: Vec<u8>, max_size: usize) -> Result<Vec<u8>, String> {
+    match compression {
+        COMPRESSION_NONE => Ok(data),
+        #[cfg(feature = "zstd")]
+        COMPRESSION_ZSTD => {
+            // `zstd::bulk::compress` always writes the content size into the frame header
+            let size = match zstd::zstd_safe::get_frame_content_size(&data) {
+                Ok(Some(size)) => size,
+                Ok(None) => return Err("Cannot decompress zstd data: no content size".to_string()),
+                Err(_err) => return Err("Cannot decompress zstd data: invalid frame header".to_string()),
+            };
+            if size > max_size as u64 {
+                return Err(format!("Decompressed data is too large: {} bytes", size));
+            }
+            zstd::bulk::decompress(&data, size as usize)
+                .or_else(|err| Err(format!("Cannot decompress zstd data: {}", err)))
+        }
+        #[cfg(feature = "lz4")]
+        COMPRESSION_LZ4 => {
+            let size = data
+                .get(..4)
+                .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+                .ok_or("Cannot decompress lz4 data: no size")?;
+            if size > max_size {
+                return Err(format!("Decompressed data is too large: {} bytes", size));
+            }
+            lz4_flex::decompress_size_prepended(&data)
+                .or_else(|err| Err(format!("Cannot decompress lz4 data: {}", err)))
+        }
+        _ => Err(format!("Unsupported compression {}", compression)),
+    }
+}
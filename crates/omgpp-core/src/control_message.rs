@@ -0,0 +1,112 @@
+use protobuf::Message;
+
+use crate::messages::general_message::{
+    general_omgpp_message::{self, Data},
+    GeneralOmgppMessage,
+};
+
+/// Namespace of message types reserved for omgpp.
+/// Internal frames (commands, rpc calls, fragments) are tagged by `GeneralOmgppMessage::data`,
+/// internal features sent as regular messages use negative `msg_type` values.
+/// Application message types (including `TypedMessage::MSG_TYPE`) must be non-negative
+pub struct ControlMessage;
+impl ControlMessage {
+    /// Greatest reserved message type, every type below zero is reserved
+    pub const RESERVED_MAX: i64 = -1;
+    /// Empty message the server sends with `NoNagle` to push the data queued before it out right away
+    /// (gns-rs doesn't wrap `FlushMessagesOnConnection`). Receivers drop it without decoding the payload
+    pub const FLUSH: i64 = -1;
+
+    pub fn is_reserved(msg_type: i64) -> bool {
+        msg_type <= ControlMessage::RESERVED_MAX
+    }
+}
+
+/// Encodes the regular message. `compression` is the compression `data` is already compressed with
+pub fn create_regular_message(
+    msg_type: i64,
+    data: &[u8],
+    compression: u32,
+) -> protobuf::Result<Vec<u8>> {
+    create_sequenced_message(msg_type, data, compression, 0)
+}
+
+/// Encodes the regular message numbered with `sequence` (see `dedup::DedupWindow`), `0` leaves it unnumbered
+pub fn create_sequenced_message(
+    msg_type: i64,
+    data: &[u8],
+    compression: u32,
+    sequence: u64,
+) -> protobuf::Result<Vec<u8>> {
+    encode_message(msg_type, data, compression, sequence, false)
+}
+
+/// Encodes the regular message numbered with `sequence` which the receiver acknowledges (see `ack::AckReceiver`)
+pub fn create_acked_message(
+    msg_type: i64,
+    data: &[u8],
+    compression: u32,
+    sequence: u64,
+) -> protobuf::Result<Vec<u8>> {
+    encode_message(msg_type, data, compression, sequence, true)
+}
+
+fn encode_message(
+    msg_type: i64,
+    data: &[u8],
+    compression: u32,
+    sequence: u64,
+    ack_requested: bool,
+) -> protobuf::Result<Vec<u8>> {
+    let mut payload = GeneralOmgppMessage::new();
+    let mut message = general_omgpp_message::Message::new();
+    message.type_ = msg_type;
+    message.compression = compression;
+    message.sequence = sequence;
+    message.ack_requested = ack_requested;
+    message.data = Vec::from(data); // somehow get rid of unessesary array copying
+    payload.data = Some(Data::Message(message));
+    payload.write_to_bytes()
+}
+
+/// Encodes `ControlMessage::FLUSH`
+pub fn create_flush_message() -> protobuf::Result<Vec<u8>> {
+    encode_message(ControlMessage::FLUSH, &[], 0, 0, false)
+}
+
+pub fn create_rpc_message(
+    reliable: bool,
+    method_id: i64,
+    request_id: u64,
+    arg_type: i64,
+    data: Option<&[u8]>,
+    is_reply: bool,
+) -> protobuf::Result<Vec<u8>> {
+    let mut payload = GeneralOmgppMessage::new();
+    let mut rpc = general_omgpp_message::RpcCall::new();
+    rpc.reliable = reliable;
+    rpc.is_reply = is_reply;
+    rpc.method_id = method_id;
+    rpc.request_id = request_id;
+    rpc.arg_type = arg_type;
+    rpc.arg_data = match data {
+        Some(byte_array) => Vec::from(byte_array),
+        None => Vec::new(),
+    };
+    payload.data = Some(Data::Rpc(rpc));
+    payload.write_to_bytes()
+}
+
+pub fn create_cmd_message(
+    cmd: String,
+    request_id: u64,
+    args: Vec<String>,
+) -> protobuf::Result<Vec<u8>> {
+    let mut payload = GeneralOmgppMessage::new();
+    let mut request = general_omgpp_message::CmdRequest::new();
+    request.cmd = cmd;
+    request.request_id = request_id;
+    request.args = args;
+    payload.data = Some(Data::Cmd(request));
+    payload.write_to_bytes()
+}
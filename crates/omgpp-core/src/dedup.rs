@@ -0,0 +1,37 @@
+use std::collections::{HashSet, VecDeque};
+
+/// Remembers sequence numbers of the last `window_size` received unreliable messages to drop duplicates.
+/// A duplicate arriving after more than `window_size` other messages is not detected.
+/// Sequence `0` means the message is not numbered, it is never a duplicate
+#[derive(Debug, Default)]
+pub struct DedupWindow {
+    window_size: usize,
+    seen: HashSet<u64>,
+    // the same sequences as `seen`, oldest first
+    order: VecDeque<u64>,
+}
+
+impl DedupWindow {
+    pub fn new(window_size: usize) -> DedupWindow {
+        DedupWindow {
+            window_size,
+            ..Default::default()
+        }
+    }
+    /// Returns true if the sequence was already received within the window, otherwise remembers it
+    pub fn is_duplicate(&mut self, sequence: u64) -> bool {
+        if sequence == 0 || self.window_size == 0 {
+            return false;
+        }
+        if !self.seen.insert(sequence) {
+            return true;
+        }
+        self.order.push_back(sequence);
+        if self.order.len() > self.window_size {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,45 @@
+//! Application level encryption of regular messages with a pre-shared key (ChaCha20-Poly1305),
+//! for end-to-end secrecy when the traffic goes through an untrusted relay.
+//! Both sides must use the same key: the receiver with a key rejects every message which can't be decrypted
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+
+pub const KEY_SIZE: usize = 32;
+/// Every encrypted frame starts with a random nonce, followed by the ciphertext and 16 byte tag.
+/// Random nonces may collide after ~2^32 messages encrypted with the same key, rotate keys long before that
+pub const NONCE_SIZE: usize = 12;
+
+pub struct Encryption {
+    cipher: ChaCha20Poly1305,
+}
+
+impl Encryption {
+    pub fn new(key: &[u8; KEY_SIZE]) -> Encryption {
+        Encryption {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+    pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, data)
+            .or(Err("Cannot encrypt data".to_string()))?;
+        let mut frame = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        frame.extend_from_slice(&nonce);
+        frame.extend(ciphertext);
+        Ok(frame)
+    }
+    /// Fails if the frame was not encrypted with the same key or was tampered with
+    pub fn decrypt(&self, frame: &[u8]) -> Result<Vec<u8>, String> {
+        if frame.len() < NONCE_SIZE {
+            return Err(format!("Encrypted frame is too short: {} bytes", frame.len()));
+        }
+        let (nonce, ciphertext) = frame.split_at(NONCE_SIZE);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .or(Err("Cannot decrypt frame: authentication failed".to_string()))
+    }
+}
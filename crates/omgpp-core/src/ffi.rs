@@ -1,7 +1,29 @@
-use std::net::IpAddr;
+use std::{
+    ffi::{c_char, CStr},
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    str::FromStr,
+};
 use super::Endpoint;
 use uuid::Uuid;
 
+/// Returned by FFI functions shared by the client and the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum CoreFfiError {
+    None = 0,
+    NullPointer = 1,
+    InvalidEncoding = 2, // string is not valid UTF-8
+    InvalidUuid = 3,     // string is not a uuid
+    BufferTooSmall = 4,
+    Panicked = 5,
+}
+
+// a panic must not unwind into the caller
+fn catch_panic(body: impl FnOnce() -> CoreFfiError) -> CoreFfiError {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(CoreFfiError::Panicked)
+}
+
 pub trait ToFfi<T> {
     fn to_ffi(&self) -> T;
 }
@@ -33,4 +55,51 @@ pub struct EndpointFFI {
 #[repr(C,packed)]
 pub struct UuidFFI {
     pub bytes:[u8;16]
-}
\ No newline at end of file
+}
+
+/// Byte slice passed over FFI. `None` if `data` is null while `size` is not zero
+pub unsafe fn slice_from_ffi_ptr<'d>(data: *const u8, offset: isize, size: usize) -> Option<&'d [u8]> {
+    match (data.is_null(), size) {
+        (_, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(core::slice::from_raw_parts(data.offset(offset), size)),
+    }
+}
+
+/// Writes the hyphenated form of `uuid` (e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`) into `out` as a NUL-terminated string.
+/// `len` is the size of `out`, it must be at least 37 bytes, otherwise `BufferTooSmall` is returned and nothing is written
+#[no_mangle]
+pub unsafe extern "C" fn uuid_to_string(uuid: *const UuidFFI, out: *mut c_char, len: usize) -> CoreFfiError {
+    catch_panic(|| {
+        let (Some(uuid), false) = (uuid.as_ref(), out.is_null()) else {
+            return CoreFfiError::NullPointer;
+        };
+        let text = Uuid::from_bytes(uuid.bytes).hyphenated().to_string();
+        if len <= text.len() {
+            return CoreFfiError::BufferTooSmall;
+        }
+        std::ptr::copy_nonoverlapping(text.as_ptr() as *const c_char, out, text.len());
+        out.add(text.len()).write(0);
+        CoreFfiError::None
+    })
+}
+/// Parses a uuid in any of the common forms (hyphenated, simple, braced or urn) into `out_uuid`.
+/// Malformed uuids are reported as `InvalidUuid`
+#[no_mangle]
+pub unsafe extern "C" fn uuid_from_string(string: *const c_char, out_uuid: *mut UuidFFI) -> CoreFfiError {
+    catch_panic(|| {
+        let (Some(out_uuid), false) = (out_uuid.as_mut(), string.is_null()) else {
+            return CoreFfiError::NullPointer;
+        };
+        let Ok(text) = CStr::from_ptr(string).to_str() else {
+            return CoreFfiError::InvalidEncoding;
+        };
+        match Uuid::from_str(text) {
+            Ok(uuid) => {
+                *out_uuid = uuid.to_ffi();
+                CoreFfiError::None
+            }
+            Err(_) => CoreFfiError::InvalidUuid,
+        }
+    })
+}
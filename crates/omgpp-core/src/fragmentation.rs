@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use protobuf::Message;
+
+use crate::{
+    messages::general_message::{
+        general_omgpp_message::{Data, Fragment},
+        GeneralOmgppMessage,
+    },
+    MAX_MESSAGE_SIZE,
+};
+
+/// Payload bytes carried by a single fragment. The rest is left for the fragment header
+pub const FRAGMENT_DATA_SIZE: usize = MAX_MESSAGE_SIZE - 64;
+/// Messages split into more fragments are dropped by the receiving side (64MB)
+pub const MAX_FRAGMENTS: u32 = 128;
+/// Largest (compressed and encrypted) payload which can be sent in fragments
+pub const MAX_FRAGMENTED_SIZE: usize = MAX_FRAGMENTS as usize * FRAGMENT_DATA_SIZE;
+/// Messages of a single sender reassembled at once. A fragment of one more message evicts the one updated least recently
+pub const MAX_PARTIAL_MESSAGES: usize = 4;
+/// Partially received messages not updated for this long are dropped
+pub const PARTIAL_MESSAGE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Splits the regular message into encoded fragments.
+/// `compression` is the compression `data` is already compressed with.
+/// Fragments must be sent reliably and in order. Callers check `data` fits `MAX_FRAGMENTED_SIZE`,
+/// the receiving side drops messages split into more than `MAX_FRAGMENTS` fragments
+pub fn create_fragment_messages(
+    message_id: u64,
+    msg_type: i64,
+    compression: u32,
+    data: &[u8],
+) -> protobuf::Result<Vec<Vec<u8>>> {
+    let total = data.len().div_ceil(FRAGMENT_DATA_SIZE);
+    data.chunks(FRAGMENT_DATA_SIZE)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut payload = GeneralOmgppMessage::new();
+            let mut fragment = Fragment::new();
+            fragment.message_id = message_id;
+            fragment.index = index as u32;
+            fragment.total = total as u32;
+            fragment.type_ = msg_type;
+            fragment.compression = compression;
+            fragment.data = Vec::from(chunk);
+            payload.data = Some(Data::Fragment(fragment));
+            payload.write_to_bytes()
+        })
+        .collect()
+}
+
+struct PartialMessage {
+    msg_type: i64,
+    compression: u32,
+    total: u32,
+    chunks: Vec<Vec<u8>>,
+    updated_at: Instant,
+}
+
+/// Collects fragments of a single sender
+#[derive(Default)]
+pub struct FragmentAssembler {
+    partial: HashMap<u64, PartialMessage>,
+}
+
+impl FragmentAssembler {
+    /// Returns type, compression and data of the message once its last fragment is pushed
+    pub fn push(&mut self, fragment: Fragment) -> Option<(i64, u32, Vec<u8>)> {
+        if fragment.total == 0 || fragment.total > MAX_FRAGMENTS || fragment.index >= fragment.total
+        {
+            return None;
+        }
+        let now = Instant::now();
+        self.evict_partial(fragment.message_id, now);
+        let partial = self
+            .partial
+            .entry(fragment.message_id)
+            .or_insert_with(|| PartialMessage {
+                msg_type: fragment.type_,
+                compression: fragment.compression,
+                total: fragment.total,
+                chunks: Vec::new(),
+                updated_at: now,
+            });
+        // fragments are sent reliably, so anything out of order means the message is broken
+        if partial.total != fragment.total || partial.chunks.len() as u32 != fragment.index {
+            self.partial.remove(&fragment.message_id);
+            return None;
+        }
+        partial.chunks.push(fragment.data);
+        partial.updated_at = now;
+        if partial.chunks.len() as u32 != partial.total {
+            return None;
+        }
+        self.partial.remove(&fragment.message_id).map(|partial| {
+            (
+                partial.msg_type,
+                partial.compression,
+                partial.chunks.concat(),
+            )
+        })
+    }
+    // drops stale messages (a sender may never finish one) and makes room for a new one
+    fn evict_partial(&mut self, message_id: u64, now: Instant) {
+        self.partial
+            .retain(|_message_id, partial| now - partial.updated_at < PARTIAL_MESSAGE_TIMEOUT);
+        if self.partial.contains_key(&message_id) || self.partial.len() < MAX_PARTIAL_MESSAGES {
+            return;
+        }
+        let least_recent = self
+            .partial
+            .iter()
+            .min_by_key(|(_message_id, partial)| partial.updated_at)
+            .map(|(message_id, _partial)| *message_id);
+        if let Some(least_recent) = least_recent {
+            self.partial.remove(&least_recent);
+        }
+    }
+}
@@ -6,19 +6,35 @@
 
 pub mod ffi;
 pub  mod cmd_handler;
+pub mod send_flags;
+pub mod fragmentation;
+pub mod compression;
+pub mod typed_message;
+pub mod control_message;
+pub mod rpc_registry;
+pub mod dedup;
+pub mod ack;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 
-use std::{net::IpAddr, sync::LazyLock};
+use std::{
+    net::IpAddr,
+    sync::{Mutex, OnceLock},
+};
 
 use either::Either;
-use gns::{GnsGlobal, GnsUtils, GnsDroppable, IsReady, GnsConnection, GnsSocket, GnsConnectionInfo};
+use gns::{GnsGlobal, GnsUtils, GnsDroppable, IsReady, GnsConnection, GnsSocket, GnsConnectionInfo, GnsConnectionRealTimeStatus, GnsNetworkMessage, ToSend};
 
 pub mod messages{
     include!(concat!(env!("OUT_DIR"), "/proto/mod.rs"));
 }
 
 
+/// Converts to and from its `i16` discriminant, e.g. to store or forward state transitions.
+/// Serializable by name with the `serde` feature
 #[allow(dead_code)]
 #[derive(Debug,Clone,Hash,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(i16)]
 pub enum ConnectionState {
     None = -1,
@@ -27,10 +43,48 @@ pub enum ConnectionState {
     Connecting = 2,
     ConnectedUnverified = 3,
     Connected = 4,
+    /// Connection was lost without being closed by either side: the peer stopped replying
+    /// within the connection timeout. Otherwise the same as `Disconnected`
+    TimedOut = 5,
+    /// Client resumed its previous session (see `OmgppPredefinedCmd::RESUME`) and passed authentication.
+    /// Reported instead of `Connected`, the client keeps the uuid of the previous session.
+    /// Only passed to state callbacks, the tracked state is `Connected`
+    Reconnected = 6,
+}
+impl From<ConnectionState> for i16 {
+    fn from(state: ConnectionState) -> Self {
+        state as i16
+    }
+}
+impl TryFrom<i16> for ConnectionState {
+    type Error = i16;
+
+    /// Returns the value back if it is not a discriminant of any state
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            -1 => Ok(ConnectionState::None),
+            0 => Ok(ConnectionState::Disconnected),
+            1 => Ok(ConnectionState::Disconnecting),
+            2 => Ok(ConnectionState::Connecting),
+            3 => Ok(ConnectionState::ConnectedUnverified),
+            4 => Ok(ConnectionState::Connected),
+            5 => Ok(ConnectionState::TimedOut),
+            6 => Ok(ConnectionState::Reconnected),
+            _ => Err(value),
+        }
+    }
 }
 
 
 
+/// Why the connection was closed.
+/// `end_reason` is one of `ESteamNetConnectionEnd` codes, `debug` is a human readable explanation
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct DisconnectReason {
+    pub end_reason: i32,
+    pub debug: String,
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct Endpoint {
     pub ip: IpAddr,
@@ -42,6 +96,12 @@ impl OmgppPredefinedCmd {
     pub const AUTH: &str = "omgpp_auth";
     // returns where server resources are located. Usually it's a HTTP server URL
     pub const RESOURCES: &str = "omgpp_resources";
+    // application level ping, the other side echoes the command back with the same request id
+    pub const PING: &str = "omgpp_ping";
+    // sent by the client before auth with the session token received in the previous auth reply
+    pub const RESUME: &str = "omgpp_resume";
+    // acknowledges unreliable messages sent with an ack request: the latest sequence and bits of the 32 before it
+    pub const ACK: &str = "omgpp_ack";
 }
 
 pub struct GnsWrapper {
@@ -51,12 +111,51 @@ pub struct GnsWrapper {
 unsafe impl Send for GnsWrapper {}
 unsafe impl Sync for GnsWrapper {}
 
-pub static GNS: LazyLock<Result<GnsWrapper,String>> = LazyLock::new(|| {
-    Ok(GnsWrapper {
-        global: GnsGlobal::get()?,
-        utils: GnsUtils::new().ok_or("Error occurred when creating GnsUtils")?,
-    })
-});
+static GNS: OnceLock<GnsWrapper> = OnceLock::new();
+// why the last initialization attempt failed
+static GNS_INIT_ERROR: Mutex<Option<String>> = Mutex::new(None);
+
+/// Initializes GNS on the first call. A failure is remembered and returned
+/// by every following call until `gns_reinit` succeeds
+pub fn gns() -> Result<&'static GnsWrapper, String> {
+    init_gns(false)
+}
+/// Re-attempts GNS initialization after it failed, e.g. once the GameNetworkingSockets library
+/// becomes available after a delayed dependency loads. Does nothing if GNS is already initialized
+pub fn gns_reinit() -> Result<&'static GnsWrapper, String> {
+    init_gns(true)
+}
+fn init_gns(retry: bool) -> Result<&'static GnsWrapper, String> {
+    if let Some(gns) = GNS.get() {
+        return Ok(gns);
+    }
+    let mut init_error = GNS_INIT_ERROR
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    // initialized by another thread while we were waiting for the lock
+    if let Some(gns) = GNS.get() {
+        return Ok(gns);
+    }
+    if let (Some(err), false) = (init_error.as_ref(), retry) {
+        return Err(err.clone());
+    }
+    let wrapper = (|| -> Result<GnsWrapper, String> {
+        Ok(GnsWrapper {
+            global: GnsGlobal::get()?,
+            utils: GnsUtils::new().ok_or("Error occurred when creating GnsUtils")?,
+        })
+    })();
+    match wrapper {
+        Ok(wrapper) => {
+            *init_error = None;
+            Ok(GNS.get_or_init(|| wrapper))
+        }
+        Err(err) => {
+            *init_error = Some(err.clone());
+            Err(err)
+        }
+    }
+}
 
 pub trait ToEndpoint {
     fn to_endpoint(&self) -> Endpoint;
@@ -70,6 +169,87 @@ impl ToEndpoint for GnsConnectionInfo {
     }
 }
 
+/// Number of connection events and messages handled by a single `process` call.
+/// If any of them reached `N` the queue may be not drained yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct ProcessStats {
+    pub events: usize,
+    pub messages: usize,
+}
+
+/// Snapshot of the GNS real-time connection status
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[repr(C)]
+pub struct ConnectionRealTimeStatus {
+    pub ping: i32,
+    pub connection_quality_local: f32,
+    pub connection_quality_remote: f32,
+    pub out_packets_per_sec: f32,
+    pub out_bytes_per_sec: f32,
+    pub in_packets_per_sec: f32,
+    pub in_bytes_per_sec: f32,
+    pub send_rate_bytes_per_sec: i32,
+    pub pending_unreliable: i32,
+    pub pending_reliable: i32,
+    pub sent_unacked_reliable: i32,
+}
+
+/// Application level traffic of a single connection, counted from the moment it was tracked.
+/// Bytes include omgpp framing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct TrafficStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+}
+
+/// Application level traffic of a single regular message type over all connections.
+/// Bytes include omgpp framing, fragmented messages are counted once with bytes of all their fragments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct TypeStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+}
+
+pub trait ToConnectionStatus {
+    fn to_connection_status(&self) -> ConnectionRealTimeStatus;
+}
+impl ToConnectionStatus for GnsConnectionRealTimeStatus {
+    fn to_connection_status(&self) -> ConnectionRealTimeStatus {
+        ConnectionRealTimeStatus {
+            ping: self.ping() as _,
+            connection_quality_local: self.connection_quality_local() as _,
+            connection_quality_remote: self.connection_quality_remote() as _,
+            out_packets_per_sec: self.out_packets_per_sec() as _,
+            out_bytes_per_sec: self.out_bytes_per_sec() as _,
+            in_packets_per_sec: self.in_packets_per_sec() as _,
+            in_bytes_per_sec: self.in_bytes_per_sec() as _,
+            send_rate_bytes_per_sec: self.send_rate_bytes_per_sec() as _,
+            pending_unreliable: self.pending_unreliable() as _,
+            pending_reliable: self.pending_reliable() as _,
+            sent_unacked_reliable: self.sent_unacked_reliable() as _,
+        }
+    }
+}
+
+/// Priority and weight of a connection lane, see `ConfigureConnectionLanes` in steamnetworkingsockets.h.
+/// Lanes with lower priority value are sent first, lanes of the same priority share bandwidth by weight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct LaneConfig {
+    pub priority: i32,
+    pub weight: u16,
+}
+
+/// Max size of a single message accepted by GNS, both reliable and unreliable
+/// (k_cbMaxSteamNetworkingSocketsMessageSizeSend in steamnetworkingtypes.h)
+pub const MAX_MESSAGE_SIZE: usize = 512 * 1024;
 
 #[allow(dead_code)]
 pub struct TransmitterHelper {}
@@ -93,23 +273,53 @@ impl TransmitterHelper {
         connections: impl Iterator<Item = GnsConnection>,
         flags: i32,
         data: &[u8],
+    ) -> Vec<Either<u64, gns_sys::EResult>> {
+        TransmitterHelper::send_on_lane(socket, connections, flags, 0, data)
+    }
+    /// Lane must be configured on every connection beforehand, otherwise GNS fails the message
+    pub fn send_on_lane<T: GnsDroppable + IsReady>(
+        socket: &GnsSocket<'_, '_, T>,
+        connections: impl Iterator<Item = GnsConnection>,
+        flags: i32,
+        lane: u16,
+        data: &[u8],
     ) -> Vec<Either<u64, gns_sys::EResult>> {
         let messages = connections
             .map(|connection| {
                 socket
                     .utils()
                     .allocate_message(connection.clone(), flags, data)
+                    .set_lane(lane)
+            })
+            .collect::<Vec<_>>();
+
+        TransmitterHelper::send_messages(socket, messages)
+    }
+    /// Sends different messages within a single `send_messages` call
+    pub fn send_batch<'d, T: GnsDroppable + IsReady>(
+        socket: &GnsSocket<'_, '_, T>,
+        messages: impl Iterator<Item = (GnsConnection, i32, u16, &'d [u8])>,
+    ) -> Vec<Either<u64, gns_sys::EResult>> {
+        let messages = messages
+            .map(|(connection, flags, lane, data)| {
+                socket.utils().allocate_message(connection, flags, data).set_lane(lane)
             })
             .collect::<Vec<_>>();
 
-        match messages.len() > 0 {
-            true => socket.send_messages(messages),
-            false => vec![],
+        TransmitterHelper::send_messages(socket, messages)
+    }
+    /// Returns exactly one result per message. GNS may report fewer results than messages submitted
+    /// (e.g. when the connection is being closed), the missing ones are treated as failed
+    fn send_messages<T: GnsDroppable + IsReady>(
+        socket: &GnsSocket<'_, '_, T>,
+        messages: Vec<GnsNetworkMessage<ToSend>>,
+    ) -> Vec<Either<u64, gns_sys::EResult>> {
+        let message_count = messages.len();
+        if message_count == 0 {
+            return vec![];
         }
-        /*
-            if res.get(0).unwrap().is_right() {
-                return ServerResult::Err("Some error occured when sending the message".to_string());
-            }
-        */
+        let mut results = socket.send_messages(messages);
+        results.resize(message_count, Either::Right(gns_sys::EResult::k_EResultFail));
+        results
     }
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::Endpoint;
+
+/// Argument or reply of a registered rpc method
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RpcArgs {
+    pub arg_type: i64,
+    pub data: Vec<u8>,
+}
+impl RpcArgs {
+    pub fn new(arg_type: i64, data: Vec<u8>) -> RpcArgs {
+        RpcArgs { arg_type, data }
+    }
+}
+
+// returned args are sent back as the reply, `None` means the method does not reply
+type RpcMethodHandler<T> = Box<dyn Fn(&T, &Uuid, &Endpoint, RpcArgs) -> Option<RpcArgs> + 'static>;
+
+/// Rpc methods dispatched by `method_id`.
+/// Calls of methods which are not registered are passed to `on_rpc` callback
+pub struct RpcRegistry<T> {
+    methods: HashMap<i64, RpcMethodHandler<T>>,
+}
+impl<T> RpcRegistry<T> {
+    pub fn new() -> RpcRegistry<T> {
+        RpcRegistry {
+            methods: Default::default(),
+        }
+    }
+    pub fn register(&mut self, method_id: i64, handler: RpcMethodHandler<T>) -> Result<(), String> {
+        if self.methods.contains_key(&method_id) {
+            return Err(format!("Rpc method {} already registered", method_id));
+        }
+        self.methods.insert(method_id, handler);
+        Ok(())
+    }
+    /// Returns false if the method was not registered
+    pub fn unregister(&mut self, method_id: i64) -> bool {
+        self.methods.remove(&method_id).is_some()
+    }
+    pub fn contains(&self, method_id: i64) -> bool {
+        self.methods.contains_key(&method_id)
+    }
+    /// Invokes the method's handler and returns its reply. `None` if the method is not registered
+    pub fn handle(
+        &self,
+        item: &T,
+        uuid: &Uuid,
+        endpoint: &Endpoint,
+        method_id: i64,
+        args: RpcArgs,
+    ) -> Option<RpcArgs> {
+        let handler = self.methods.get(&method_id)?;
+        handler(item, uuid, endpoint, args)
+    }
+}
@@ -0,0 +1,48 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use gns_sys::{
+    k_nSteamNetworkingSend_NoDelay, k_nSteamNetworkingSend_NoNagle,
+    k_nSteamNetworkingSend_Reliable, k_nSteamNetworkingSend_Unreliable,
+    k_nSteamNetworkingSend_UseCurrentThread,
+};
+
+/// GNS `k_nSteamNetworkingSend_*` flags. Flags are combined with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SendFlags(i32);
+
+impl SendFlags {
+    pub const UNRELIABLE: SendFlags = SendFlags(k_nSteamNetworkingSend_Unreliable);
+    pub const RELIABLE: SendFlags = SendFlags(k_nSteamNetworkingSend_Reliable);
+    /// Send the message immediately instead of waiting for more messages to batch them (Nagle)
+    pub const NO_NAGLE: SendFlags = SendFlags(k_nSteamNetworkingSend_NoNagle);
+    /// Drop an unreliable message if it cannot be sent right away
+    pub const NO_DELAY: SendFlags = SendFlags(k_nSteamNetworkingSend_NoDelay);
+    pub const USE_CURRENT_THREAD: SendFlags = SendFlags(k_nSteamNetworkingSend_UseCurrentThread);
+
+    pub fn bits(&self) -> i32 {
+        self.0
+    }
+    pub fn contains(&self, other: SendFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for SendFlags {
+    fn default() -> Self {
+        SendFlags::UNRELIABLE
+    }
+}
+
+impl BitOr for SendFlags {
+    type Output = SendFlags;
+
+    fn bitor(self, rhs: SendFlags) -> SendFlags {
+        SendFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for SendFlags {
+    fn bitor_assign(&mut self, rhs: SendFlags) {
+        self.0 |= rhs.0;
+    }
+}
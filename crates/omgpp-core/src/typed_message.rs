@@ -0,0 +1,9 @@
+/// Message with its own type id and (de)serialization, see `Server::send_msg`/`Server::register_on_typed_message`
+pub trait TypedMessage: Sized {
+    /// Passed as `msg_type`, must be unique among the application messages and non-negative
+    /// (see `ControlMessage`)
+    const MSG_TYPE: i64;
+
+    fn encode(&self) -> Vec<u8>;
+    fn decode(data: &[u8]) -> Result<Self, String>;
+}
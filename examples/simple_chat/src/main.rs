@@ -35,13 +35,13 @@ fn start_server() {
     println!("Hello! Im Server");
     let server = Server::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 55655).unwrap();
     server.register_on_connect_requested(|_server,_id, _endpoint| true);
-    server.register_on_connection_state_changed(|server,id, endpoint, state| {
+    server.register_on_connection_state_changed(|server,id, endpoint, state, reason| {
         let msg= format!("Client {:?} {:?}",endpoint,state);
         let status  = server.broadcast(0,msg.as_bytes());
 
-        println!("{:?} {:?} {:?} {:?}", id, state,msg, status)
+        println!("{:?} {:?} {:?} {:?} {:?}", id, state,msg, status, reason)
     });
-    server.register_on_message(|ser,id, _endpoint,msg_type, data| {
+    server.register_on_message(|ser,id, _endpoint,_reliable,msg_type, data| {
         _ =ser.broadcast(msg_type,data.as_slice());
      println!(
             "Message from: {:?} Type: {:?} Data: {:?}",
@@ -75,15 +75,15 @@ fn start_client() {
 
         client.register_on_connection_state_changed(move |client,endpoint, state| {
             println!("{:?} {:?}", endpoint, state);
-            if state == ConnectionState::Disconnected {
+            if matches!(state, ConnectionState::Disconnected | ConnectionState::TimedOut) {
                 // should_reconnected_cloned.set(true);
             }
-            if state == ConnectionState::Connected{
+            if matches!(state, ConnectionState::Connected | ConnectionState::Reconnected) {
                 _= client.send(1, "IM HERE".as_bytes());
             }
         });
 
-        client.register_on_message(|_client,endpoint, msg_type, data| {
+        client.register_on_message(|_client,endpoint, _reliable, msg_type, data| {
             println!(
                 "Server says: {:?} Type: {:?} Data: {:?}",
                 endpoint,